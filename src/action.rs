@@ -0,0 +1,566 @@
+use bevy::{
+    input::{
+        gamepad::{
+            Gamepad, GamepadAxis, GamepadAxisType, GamepadButton, GamepadButtonType, Gamepads,
+        },
+        Axis, Input, InputSystem,
+    },
+    math::Vec2,
+    prelude::{
+        App, CoreStage, KeyCode, MouseButton, ParallelSystemDescriptorCoercion, Plugin, Query, Res,
+        ResMut,
+    },
+    utils::{HashMap, HashSet},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{input::Controlled, replay::Replay, simulation::FIXED_UPDATE};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Fire,
+    Melee,
+    Dash,
+    Sprint,
+    Reload,
+    MoveTo,
+}
+
+impl Action {
+    pub const ALL: [Action; 10] = [
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Fire,
+        Action::Melee,
+        Action::Dash,
+        Action::Sprint,
+        Action::Reload,
+        Action::MoveTo,
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButtonType),
+}
+
+impl Binding {
+    /// Held now or pressed at some point this frame, so taps within a frame still count.
+    fn pressed(
+        &self,
+        keys: &Input<KeyCode>,
+        mouse_buttons: &Input<MouseButton>,
+        gamepad_buttons: &Input<GamepadButton>,
+        gamepads: &[Gamepad],
+    ) -> bool {
+        match *self {
+            Binding::Key(key) => keys.pressed(key) || keys.just_pressed(key),
+            Binding::Mouse(button) => {
+                mouse_buttons.pressed(button) || mouse_buttons.just_pressed(button)
+            }
+            Binding::Gamepad(button) => gamepads.iter().any(|gamepad| {
+                let button = GamepadButton(*gamepad, button);
+                gamepad_buttons.pressed(button) || gamepad_buttons.just_pressed(button)
+            }),
+        }
+    }
+}
+
+pub struct InputBindings {
+    bindings: HashMap<Action, Vec<Binding>>,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        let mut bindings = Self::empty();
+        bindings
+            .bind(Action::MoveUp, Binding::Key(KeyCode::W))
+            .bind(Action::MoveUp, Binding::Gamepad(GamepadButtonType::DPadUp))
+            .bind(Action::MoveDown, Binding::Key(KeyCode::S))
+            .bind(
+                Action::MoveDown,
+                Binding::Gamepad(GamepadButtonType::DPadDown),
+            )
+            .bind(Action::MoveLeft, Binding::Key(KeyCode::A))
+            .bind(
+                Action::MoveLeft,
+                Binding::Gamepad(GamepadButtonType::DPadLeft),
+            )
+            .bind(Action::MoveRight, Binding::Key(KeyCode::D))
+            .bind(
+                Action::MoveRight,
+                Binding::Gamepad(GamepadButtonType::DPadRight),
+            )
+            .bind(Action::Fire, Binding::Mouse(MouseButton::Left))
+            .bind(
+                Action::Fire,
+                Binding::Gamepad(GamepadButtonType::RightTrigger2),
+            )
+            .bind(Action::Melee, Binding::Key(KeyCode::F))
+            .bind(
+                Action::Melee,
+                Binding::Gamepad(GamepadButtonType::RightTrigger),
+            )
+            .bind(Action::Dash, Binding::Key(KeyCode::Space))
+            .bind(Action::Dash, Binding::Gamepad(GamepadButtonType::South))
+            .bind(Action::Sprint, Binding::Key(KeyCode::LShift))
+            .bind(
+                Action::Sprint,
+                Binding::Gamepad(GamepadButtonType::LeftThumb),
+            )
+            .bind(Action::Reload, Binding::Key(KeyCode::R))
+            .bind(Action::Reload, Binding::Gamepad(GamepadButtonType::West))
+            .bind(Action::MoveTo, Binding::Mouse(MouseButton::Right));
+        bindings
+    }
+}
+
+impl InputBindings {
+    pub fn empty() -> Self {
+        Self {
+            bindings: HashMap::default(),
+        }
+    }
+
+    pub fn from_map(map: impl IntoIterator<Item = (Action, Vec<Binding>)>) -> Self {
+        let mut bindings = Self::empty();
+        for (action, bound) in map {
+            bindings.clear(action);
+            for binding in bound {
+                bindings.bind(action, binding);
+            }
+        }
+        bindings
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Action, &[Binding])> {
+        self.bindings
+            .iter()
+            .map(|(action, bindings)| (*action, bindings.as_slice()))
+    }
+
+    pub fn conflicts(&self) -> Vec<(Binding, Vec<Action>)> {
+        let mut actions_by_binding: HashMap<Binding, Vec<Action>> = HashMap::default();
+        for (action, bindings) in self.iter() {
+            for binding in bindings {
+                actions_by_binding.entry(*binding).or_default().push(action);
+            }
+        }
+
+        actions_by_binding
+            .into_iter()
+            .filter(|(_, actions)| actions.len() > 1)
+            .map(|(binding, mut actions)| {
+                actions.sort();
+                (binding, actions)
+            })
+            .collect()
+    }
+
+    pub fn bind(&mut self, action: Action, binding: Binding) -> &mut Self {
+        let bindings = self.bindings.entry(action).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+        self
+    }
+
+    pub fn unbind(&mut self, action: Action, binding: Binding) -> &mut Self {
+        if let Some(bindings) = self.bindings.get_mut(&action) {
+            bindings.retain(|bound| *bound != binding);
+        }
+        self
+    }
+
+    /// Leaves `action` with no bindings. It stays listed, so saved settings keep it unbound
+    /// instead of falling back to the defaults.
+    pub fn clear(&mut self, action: Action) -> &mut Self {
+        self.bindings.entry(action).or_default().clear();
+        self
+    }
+
+    pub fn bindings(&self, action: Action) -> &[Binding] {
+        self.bindings
+            .get(&action)
+            .map(|bindings| bindings.as_slice())
+            .unwrap_or(&[])
+    }
+}
+
+pub struct AnalogSettings {
+    pub move_dead_zone: f32,
+    pub aim_dead_zone: f32,
+}
+
+impl Default for AnalogSettings {
+    fn default() -> Self {
+        Self {
+            move_dead_zone: 0.2,
+            aim_dead_zone: 0.3,
+        }
+    }
+}
+
+fn radial_dead_zone(stick: Vec2, dead_zone: f32) -> Vec2 {
+    let length = stick.length();
+    if length <= dead_zone || dead_zone >= 1.0 {
+        return Vec2::ZERO;
+    }
+
+    let magnitude = ((length - dead_zone) / (1.0 - dead_zone)).min(1.0);
+    stick / length * magnitude
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InputSource {
+    Local,
+    Gamepad(Gamepad),
+}
+
+#[derive(Default, Debug)]
+pub struct ActionSet {
+    pressed: HashSet<Action>,
+    pressed_at: HashMap<Action, u64>,
+    just_pressed: HashSet<Action>,
+    just_released: HashSet<Action>,
+    move_stick: Vec2,
+    aim_stick: Vec2,
+}
+
+impl ActionSet {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn pressed_actions(&self) -> impl Iterator<Item = Action> + '_ {
+        self.pressed.iter().copied()
+    }
+
+    pub fn pressed_at(&self, action: Action) -> Option<u64> {
+        self.pressed_at.get(&action).copied()
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    pub fn just_released(&self, action: Action) -> bool {
+        self.just_released.contains(&action)
+    }
+
+    pub fn move_stick(&self) -> Vec2 {
+        self.move_stick
+    }
+
+    pub fn aim_stick(&self) -> Option<Vec2> {
+        if self.aim_stick == Vec2::ZERO {
+            None
+        } else {
+            Some(self.aim_stick)
+        }
+    }
+
+    pub(crate) fn update(
+        &mut self,
+        tick: u64,
+        pressed: HashSet<Action>,
+        move_stick: Vec2,
+        aim_stick: Vec2,
+    ) {
+        self.just_pressed = pressed.difference(&self.pressed).copied().collect();
+        self.just_released = self.pressed.difference(&pressed).copied().collect();
+        self.pressed = pressed;
+
+        self.pressed_at
+            .retain(|action, _| self.pressed.contains(action));
+        for action in self.just_pressed.iter() {
+            self.pressed_at.insert(*action, tick);
+        }
+
+        self.move_stick = move_stick;
+        self.aim_stick = aim_stick;
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct ActionState {
+    tick: u64,
+    local: ActionSet,
+    gamepads: HashMap<Gamepad, ActionSet>,
+}
+
+impl ActionState {
+    pub fn local(&self) -> &ActionSet {
+        &self.local
+    }
+
+    pub fn source(&self, source: InputSource) -> Option<&ActionSet> {
+        match source {
+            InputSource::Local => Some(&self.local),
+            InputSource::Gamepad(gamepad) => self.gamepads.get(&gamepad),
+        }
+    }
+}
+
+fn read_stick(
+    axes: &Axis<GamepadAxis>,
+    gamepad: Gamepad,
+    x: GamepadAxisType,
+    y: GamepadAxisType,
+    dead_zone: f32,
+) -> Vec2 {
+    let stick = Vec2::new(
+        axes.get(GamepadAxis(gamepad, x)).unwrap_or(0.0),
+        axes.get(GamepadAxis(gamepad, y)).unwrap_or(0.0),
+    );
+    radial_dead_zone(stick, dead_zone)
+}
+
+fn strongest(sticks: impl Iterator<Item = Vec2>) -> Vec2 {
+    sticks.fold(Vec2::ZERO, |strongest, stick| {
+        if stick.length_squared() > strongest.length_squared() {
+            stick
+        } else {
+            strongest
+        }
+    })
+}
+
+/// Device state gathered every frame for one input source. Presses are latched until the
+/// next simulation tick consumes them, so a tap shorter than a tick isn't lost.
+#[derive(Default, Debug)]
+struct InputSample {
+    pressed: HashSet<Action>,
+    latched: HashSet<Action>,
+    move_stick: Vec2,
+    aim_stick: Vec2,
+}
+
+impl InputSample {
+    fn sample(&mut self, pressed: HashSet<Action>, move_stick: Vec2, aim_stick: Vec2) {
+        self.latched.extend(pressed.iter().copied());
+        self.pressed = pressed;
+        self.move_stick = move_stick;
+        self.aim_stick = aim_stick;
+    }
+
+    fn consume(&mut self) -> HashSet<Action> {
+        let mut pressed = self.pressed.clone();
+        pressed.extend(self.latched.drain());
+        pressed
+    }
+}
+
+#[derive(Default, Debug)]
+struct InputSamples {
+    local: InputSample,
+    gamepads: HashMap<Gamepad, InputSample>,
+}
+
+fn sample_inputs(
+    bindings: Res<InputBindings>,
+    keys: Res<Input<KeyCode>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepads: Res<Gamepads>,
+    axes: Res<Axis<GamepadAxis>>,
+    analog: Res<AnalogSettings>,
+    controlled: Query<&Controlled>,
+    mut samples: ResMut<InputSamples>,
+) {
+    let samples = &mut *samples;
+
+    let mut sticks = HashMap::default();
+    for gamepad in gamepads.iter() {
+        let move_stick = read_stick(
+            &axes,
+            *gamepad,
+            GamepadAxisType::LeftStickX,
+            GamepadAxisType::LeftStickY,
+            analog.move_dead_zone,
+        );
+        let aim_stick = read_stick(
+            &axes,
+            *gamepad,
+            GamepadAxisType::RightStickX,
+            GamepadAxisType::RightStickY,
+            analog.aim_dead_zone,
+        );
+        sticks.insert(*gamepad, (move_stick, aim_stick));
+
+        let pressed = bindings
+            .bindings
+            .iter()
+            .filter(|(_, bindings)| {
+                bindings.iter().any(|binding| {
+                    matches!(binding, Binding::Gamepad(_))
+                        && binding.pressed(&keys, &mouse_buttons, &gamepad_buttons, &[*gamepad])
+                })
+            })
+            .map(|(action, _)| *action)
+            .collect::<HashSet<_>>();
+
+        samples
+            .gamepads
+            .entry(*gamepad)
+            .or_default()
+            .sample(pressed, move_stick, aim_stick);
+    }
+    samples
+        .gamepads
+        .retain(|gamepad, _| gamepads.contains(gamepad));
+
+    // Pads that drive their own entity don't also drive the local player.
+    let unclaimed = gamepads
+        .iter()
+        .copied()
+        .filter(|gamepad| {
+            !controlled
+                .iter()
+                .any(|controlled| controlled.0 == InputSource::Gamepad(*gamepad))
+        })
+        .collect::<Vec<_>>();
+
+    let pressed = bindings
+        .bindings
+        .iter()
+        .filter(|(_, bindings)| {
+            bindings
+                .iter()
+                .any(|binding| binding.pressed(&keys, &mouse_buttons, &gamepad_buttons, &unclaimed))
+        })
+        .map(|(action, _)| *action)
+        .collect::<HashSet<_>>();
+
+    samples.local.sample(
+        pressed,
+        strongest(unclaimed.iter().map(|gamepad| sticks[gamepad].0)),
+        strongest(unclaimed.iter().map(|gamepad| sticks[gamepad].1)),
+    );
+}
+
+fn update_action_state(
+    replay: Option<Res<Replay>>,
+    mut samples: ResMut<InputSamples>,
+    mut state: ResMut<ActionState>,
+) {
+    let state = &mut *state;
+    state.tick += 1;
+
+    if let Some(frame) = replay.as_ref().and_then(|replay| replay.frame()) {
+        state.gamepads.clear();
+        state.local.update(
+            state.tick,
+            frame.pressed.iter().copied().collect(),
+            frame.move_stick,
+            frame.aim_stick,
+        );
+        return;
+    }
+
+    for (gamepad, sample) in samples.gamepads.iter_mut() {
+        state.gamepads.entry(*gamepad).or_default().update(
+            state.tick,
+            sample.consume(),
+            sample.move_stick,
+            sample.aim_stick,
+        );
+    }
+    state
+        .gamepads
+        .retain(|gamepad, _| samples.gamepads.contains_key(gamepad));
+
+    let local = &mut samples.local;
+    state.local.update(
+        state.tick,
+        local.consume(),
+        local.move_stick,
+        local.aim_stick,
+    );
+}
+
+#[derive(Default)]
+pub struct ActionPlugin;
+
+impl Plugin for ActionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InputBindings>()
+            .init_resource::<AnalogSettings>()
+            .init_resource::<ActionState>()
+            .init_resource::<InputSamples>()
+            .add_system_to_stage(CoreStage::PreUpdate, sample_inputs.after(InputSystem))
+            .add_system_to_stage(
+                FIXED_UPDATE,
+                update_action_state.label("update_action_state"),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bindings_can_be_changed_at_runtime() {
+        let mut bindings = InputBindings::empty();
+        bindings
+            .bind(Action::Fire, Binding::Mouse(MouseButton::Left))
+            .bind(Action::Fire, Binding::Mouse(MouseButton::Left))
+            .bind(Action::Fire, Binding::Key(KeyCode::J));
+        assert_eq!(
+            bindings.bindings(Action::Fire),
+            &[Binding::Mouse(MouseButton::Left), Binding::Key(KeyCode::J)]
+        );
+
+        bindings.unbind(Action::Fire, Binding::Mouse(MouseButton::Left));
+        assert_eq!(bindings.bindings(Action::Fire), &[Binding::Key(KeyCode::J)]);
+
+        bindings.clear(Action::Fire);
+        assert!(bindings.bindings(Action::Fire).is_empty());
+        assert!(bindings.iter().any(|(action, _)| action == Action::Fire));
+    }
+
+    #[test]
+    fn dead_zone_rescales_the_rest_of_the_range() {
+        assert_eq!(radial_dead_zone(Vec2::new(0.1, 0.0), 0.2), Vec2::ZERO);
+        assert_eq!(radial_dead_zone(Vec2::new(0.2, 0.0), 0.2), Vec2::ZERO);
+
+        let stick = radial_dead_zone(Vec2::new(0.0, 0.6), 0.2);
+        assert!((stick - Vec2::new(0.0, 0.5)).length() < 1e-6, "{}", stick);
+
+        // Past full deflection the length is clamped, not the direction.
+        let stick = radial_dead_zone(Vec2::new(0.9, 0.9), 0.2);
+        assert!((stick.length() - 1.0).abs() < 1e-6, "{}", stick);
+        assert!((stick.x - stick.y).abs() < 1e-6, "{}", stick);
+    }
+
+    #[test]
+    fn dead_zone_of_one_or_more_blocks_the_stick() {
+        for dead_zone in [1.0, 1.5] {
+            for stick in [Vec2::X, Vec2::new(2.0, 0.0), Vec2::new(-0.8, 0.8)] {
+                let result = radial_dead_zone(stick, dead_zone);
+                assert_eq!(result, Vec2::ZERO, "{} {}", stick, dead_zone);
+            }
+        }
+    }
+
+    #[test]
+    fn from_map_keeps_unbound_actions() {
+        let bindings = InputBindings::from_map([
+            (Action::Fire, vec![Binding::Key(KeyCode::J)]),
+            (Action::Melee, Vec::new()),
+        ]);
+
+        assert_eq!(bindings.bindings(Action::Fire), &[Binding::Key(KeyCode::J)]);
+        assert!(bindings.iter().any(|(action, _)| action == Action::Melee));
+        assert!(bindings.bindings(Action::Dash).is_empty());
+    }
+}
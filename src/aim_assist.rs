@@ -0,0 +1,256 @@
+use std::{
+    cmp::Ordering,
+    f32::consts::{PI, TAU},
+};
+
+use bevy::{
+    math::Vec2,
+    prelude::{Component, Query, Res, ResMut, Transform, With, Without},
+};
+
+use crate::{
+    action::InputSource,
+    crosshair::CrosshairState,
+    input::{Controlled, Player},
+    mouse::{Aim, AimSource, CursorWorldPosition},
+};
+
+/// Something aim assist can lock on to. `radius` is roughly how big it looks.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct AimTarget {
+    pub radius: f32,
+}
+
+impl Default for AimTarget {
+    fn default() -> Self {
+        Self { radius: 12.0 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AimAssistMode {
+    /// Turns more slowly while the aim passes over a target.
+    Slowdown,
+    /// Bends the aim direction toward the target.
+    Magnetism,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AimAssistSettings {
+    pub mode: AimAssistMode,
+    /// 0 disables assist, 1 is the strongest pull or slowdown.
+    pub strength: f32,
+    pub cone_degrees: f32,
+    pub range: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AimAssist {
+    pub mouse: AimAssistSettings,
+    pub gamepad: AimAssistSettings,
+}
+
+impl Default for AimAssist {
+    fn default() -> Self {
+        Self {
+            mouse: AimAssistSettings {
+                mode: AimAssistMode::Slowdown,
+                strength: 0.3,
+                cone_degrees: 6.0,
+                range: 320.0,
+            },
+            gamepad: AimAssistSettings {
+                mode: AimAssistMode::Magnetism,
+                strength: 0.6,
+                cone_degrees: 15.0,
+                range: 320.0,
+            },
+        }
+    }
+}
+
+/// Signed angle from `from` to `to`, wrapped to `-PI..PI`.
+fn angle_to(from: Vec2, to: Vec2) -> f32 {
+    let difference = to.y.atan2(to.x) - from.y.atan2(from.x);
+    (difference + PI).rem_euclid(TAU) - PI
+}
+
+/// Picks the target closest to `direction` inside the cone and range. Returns the target,
+/// the angle to it, and a weight that falls from 1 at the center of the cone to 0 at the edge.
+fn best_target(
+    settings: &AimAssistSettings,
+    origin: Vec2,
+    direction: Vec2,
+    targets: &[(Vec2, AimTarget)],
+) -> Option<(Vec2, AimTarget, f32, f32)> {
+    let half_cone = settings.cone_degrees.to_radians() * 0.5;
+    targets
+        .iter()
+        .filter_map(|(position, target)| {
+            let offset = *position - origin;
+            let distance = offset.length();
+            if distance <= f32::EPSILON || distance > settings.range {
+                return None;
+            }
+
+            let angle = angle_to(direction, offset);
+            let size = (target.radius / distance).atan();
+            let outside = (angle.abs() - size).max(0.0);
+            if outside > half_cone {
+                return None;
+            }
+
+            let weight = if half_cone > 0.0 {
+                1.0 - outside / half_cone
+            } else {
+                1.0
+            };
+            Some((*position, *target, angle, weight))
+        })
+        .min_by(|a, b| a.2.abs().partial_cmp(&b.2.abs()).unwrap_or(Ordering::Equal))
+}
+
+pub(crate) fn aim_assist(
+    assist: Res<AimAssist>,
+    source: Res<AimSource>,
+    cursor: Res<CursorWorldPosition>,
+    mut crosshair: ResMut<CrosshairState>,
+    targets: Query<(&Transform, &AimTarget), Without<Player>>,
+    mut players: Query<(&Transform, &mut Aim, Option<&Controlled>), With<Player>>,
+) {
+    crosshair.snap = None;
+    let targets = targets
+        .iter()
+        .map(|(transform, target)| (transform.translation.truncate(), *target))
+        .collect::<Vec<_>>();
+
+    for (transform, mut aim, controlled) in players.iter_mut() {
+        aim.set_slowdown(0.0);
+
+        let input = controlled.map_or(InputSource::Local, |controlled| controlled.0);
+        let gamepad = match input {
+            InputSource::Gamepad(_) => true,
+            InputSource::Local => *source == AimSource::Gamepad,
+        };
+        let settings = if gamepad {
+            &assist.gamepad
+        } else {
+            &assist.mouse
+        };
+        if settings.strength <= 0.0 {
+            continue;
+        }
+
+        let direction = match aim.direction() {
+            Some(direction) => direction,
+            None => continue,
+        };
+        let origin = transform.translation.truncate();
+        let (position, target, angle, weight) =
+            match best_target(settings, origin, direction, &targets) {
+                Some(target) => target,
+                None => continue,
+            };
+        let pull = (settings.strength * weight).clamp(0.0, 1.0);
+
+        match settings.mode {
+            AimAssistMode::Slowdown => aim.set_slowdown(pull),
+            AimAssistMode::Magnetism => {
+                let bent = direction.y.atan2(direction.x) + angle * pull;
+                aim.set_direction(Some(Vec2::new(bent.cos(), bent.sin()) * direction.length()));
+            }
+        }
+
+        if input == InputSource::Local {
+            // With a mouse only snap once the cursor is actually over the target.
+            let over_target = cursor.get().map_or(false, |cursor| {
+                cursor.distance(position) <= target.radius * 2.0
+            });
+            if gamepad || over_target {
+                crosshair.snap = Some(position);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(cone_degrees: f32) -> AimAssistSettings {
+        AimAssistSettings {
+            mode: AimAssistMode::Magnetism,
+            strength: 1.0,
+            cone_degrees,
+            range: 320.0,
+        }
+    }
+
+    fn at(degrees: f32, distance: f32, radius: f32) -> (Vec2, AimTarget) {
+        let angle = degrees.to_radians();
+        (
+            Vec2::new(angle.cos(), angle.sin()) * distance,
+            AimTarget { radius },
+        )
+    }
+
+    fn weight(settings: &AimAssistSettings, targets: &[(Vec2, AimTarget)]) -> Option<f32> {
+        best_target(settings, Vec2::ZERO, Vec2::X, targets).map(|(_, _, _, weight)| weight)
+    }
+
+    fn assert_close(actual: Option<f32>, expected: f32) {
+        let actual = actual.expect("a target");
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn weight_falls_to_zero_at_the_cone_edge() {
+        let settings = settings(20.0);
+
+        assert_close(weight(&settings, &[at(0.0, 100.0, 0.0)]), 1.0);
+        assert_close(weight(&settings, &[at(5.0, 100.0, 0.0)]), 0.5);
+        assert_close(weight(&settings, &[at(-5.0, 100.0, 0.0)]), 0.5);
+        assert_close(weight(&settings, &[at(9.99, 100.0, 0.0)]), 0.001);
+        assert_eq!(weight(&settings, &[at(10.5, 100.0, 0.0)]), None);
+    }
+
+    #[test]
+    fn target_size_widens_the_cone() {
+        let settings = settings(20.0);
+        // Covers 5 degrees either side of its center at this distance.
+        let radius = 100.0 * 5.0_f32.to_radians().tan();
+
+        assert_close(weight(&settings, &[at(4.0, 100.0, radius)]), 1.0);
+        assert_close(weight(&settings, &[at(12.0, 100.0, radius)]), 0.3);
+        assert_eq!(weight(&settings, &[at(15.5, 100.0, radius)]), None);
+    }
+
+    #[test]
+    fn picks_the_target_closest_to_the_aim() {
+        let settings = settings(20.0);
+        let targets = [
+            at(8.0, 50.0, 0.0),
+            at(-3.0, 200.0, 0.0),
+            at(1.0, 400.0, 0.0),
+        ];
+
+        let (position, _, angle, _) =
+            best_target(&settings, Vec2::ZERO, Vec2::X, &targets).expect("a target");
+        assert_eq!(position, targets[1].0);
+        assert!((angle - (-3.0_f32).to_radians()).abs() < 1e-4, "{}", angle);
+    }
+
+    #[test]
+    fn zero_cone_only_assists_over_the_target() {
+        let settings = settings(0.0);
+        let radius = 100.0 * 5.0_f32.to_radians().tan();
+
+        assert_close(weight(&settings, &[at(4.0, 100.0, radius)]), 1.0);
+        assert_eq!(weight(&settings, &[at(6.0, 100.0, radius)]), None);
+    }
+}
@@ -6,6 +6,7 @@ use bevy::prelude::{CoreStage, ParallelSystemDescriptorCoercion, Plugin, SystemS
 use bevy::reflect::TypeUuid;
 use bevy::sprite::TextureAtlasSprite;
 use bevy::{
+    asset::HandleId,
     core::Time,
     prelude::{AddAsset, App, Assets, Commands, Entity, Handle, Query, Res, Without},
 };
@@ -17,6 +18,7 @@ use std::ops::DerefMut;
 pub struct SpriteSheetAnimation {
     frames: Vec<usize>,
     fps: u8,
+    repeat: bool,
 }
 
 impl Default for SpriteSheetAnimation {
@@ -24,6 +26,7 @@ impl Default for SpriteSheetAnimation {
         Self {
             frames: [0].to_vec(),
             fps: 12,
+            repeat: true,
         }
     }
 }
@@ -37,6 +40,24 @@ impl SpriteSheetAnimation {
         }
     }
 
+    pub fn once(frames: Vec<usize>, fps: u8) -> Self {
+        Self {
+            frames,
+            fps,
+            repeat: false,
+        }
+    }
+
+    /// How long one pass over the frames takes.
+    pub fn duration(&self) -> f32 {
+        self.frames.len() as f32 / self.fps as f32
+    }
+
+    /// Position in the frame list `elapsed` seconds after the animation started.
+    pub fn frame_at(&self, elapsed: f32) -> usize {
+        (elapsed * self.fps as f32) as usize
+    }
+
     //    fn from_range(index_range: RangeInclusive<u32>) -> Self {
     //        Self::from_iter(index_range)
     //    }
@@ -46,7 +67,12 @@ impl SpriteSheetAnimation {
     //    }
 
     fn next_frame(&self, frame: usize) -> usize {
-        return frame % self.frames.len();
+        let frame = if self.repeat {
+            frame % self.frames.len()
+        } else {
+            frame.min(self.frames.len() - 1)
+        };
+        self.frames[frame]
     }
 }
 
@@ -54,6 +80,7 @@ impl SpriteSheetAnimation {
 pub struct SpriteSheetAnimationState {
     current_frame: usize,
     timer: Timer,
+    animation: HandleId,
 }
 
 impl Default for SpriteSheetAnimationState {
@@ -61,14 +88,16 @@ impl Default for SpriteSheetAnimationState {
         SpriteSheetAnimationState {
             current_frame: 0,
             timer: Timer::from_seconds(0.1, true),
+            animation: HandleId::default::<SpriteSheetAnimation>(),
         }
     }
 }
 
 impl SpriteSheetAnimationState {
-    fn new(animation: &SpriteSheetAnimation) -> Self {
+    fn new(animation: &SpriteSheetAnimation, handle: &Handle<SpriteSheetAnimation>) -> Self {
         SpriteSheetAnimationState {
             timer: Timer::from_seconds(1.0 / animation.fps as f32, true),
+            animation: handle.id,
             ..Default::default()
         }
     }
@@ -78,13 +107,26 @@ impl SpriteSheetAnimationState {
         time: &Res<Time>,
         mut sprite: impl DerefMut<Target = TextureAtlasSprite>,
         animation: &SpriteSheetAnimation,
+        handle: &Handle<SpriteSheetAnimation>,
     ) {
+        if self.animation != handle.id {
+            *self = SpriteSheetAnimationState::new(animation, handle);
+            sprite.index = animation.next_frame(self.current_frame);
+            return;
+        }
+
         self.timer.tick(time.delta());
         if self.timer.finished() {
             sprite.index = animation.next_frame(self.next());
         }
     }
 
+    /// Plays the animation from its first frame on the next update, even if it's
+    /// already the current one.
+    pub fn restart(&mut self) {
+        self.animation = HandleId::default::<SpriteSheetAnimation>();
+    }
+
     fn next(&mut self) -> usize {
         self.current_frame += 1;
         self.current_frame
@@ -100,7 +142,7 @@ pub fn add_animation_state(
         let animation = animation_defs.get(anim_handle).unwrap();
         commands
             .entity(entity)
-            .insert(SpriteSheetAnimationState::new(animation));
+            .insert(SpriteSheetAnimationState::new(animation, anim_handle));
     }
 }
 
@@ -113,16 +155,16 @@ pub fn animate(
         &mut SpriteSheetAnimationState,
     )>,
 ) {
-    for (sprite, animation, mut state) in
+    for (sprite, anim_handle, animation, mut state) in
         animations
             .iter_mut()
             .filter_map(|(sprite, anim_handle, state)| {
                 animation_defs
                     .get(anim_handle)
-                    .map(|anim| (sprite, anim, state))
+                    .map(|anim| (sprite, anim_handle, anim, state))
             })
     {
-        state.update(&time, sprite, animation);
+        state.update(&time, sprite, animation, anim_handle);
     }
 }
 
@@ -150,3 +192,21 @@ impl<T: 'static + AnimationState> Plugin for AnimationPlugin<T> {
             .add_system(animate.run_in_state(self.state).label("animate"));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_at_steps_once_per_frame_time() {
+        let animation = SpriteSheetAnimation::once(vec![4, 5, 6, 7, 8], 10);
+
+        assert!((animation.duration() - 0.5).abs() < 1e-6);
+        assert_eq!(animation.frame_at(0.0), 0);
+        assert_eq!(animation.frame_at(0.099), 0);
+        assert_eq!(animation.frame_at(0.1), 1);
+        assert_eq!(animation.frame_at(0.25), 2);
+        assert_eq!(animation.frame_at(0.499), 4);
+        assert_eq!(animation.frame_at(animation.duration()), 5);
+    }
+}
@@ -1,29 +1,433 @@
 use std::{fmt::Debug, hash::Hash};
 
 use bevy::{
+    core::{Time, Timer},
     math::Vec2,
-    prelude::{Camera, CoreStage, Local, ParamSet, Plugin, Query, SystemSet, Transform, With},
+    prelude::{
+        AddAsset, Camera, Color, Commands, Component, CoreStage, Entity, GlobalTransform, Or,
+        OrthographicProjection, ParallelSystemDescriptorCoercion, ParamSet, Plugin, Query, Res,
+        SpriteBundle, Transform, With, Without,
+    },
+    render::camera::RenderTarget,
+    sprite::{Sprite, TextureAtlasSprite},
+    transform::TransformSystem,
+    window::Windows,
 };
-use iyes_loopless::prelude::IntoConditionalSystem;
+use iyes_loopless::prelude::{AppLooplessStateExt, IntoConditionalSystem};
+use serde::{Deserialize, Serialize};
 
-use crate::input::Player;
+use crate::{
+    camera_path::{
+        play_camera_paths, start_camera_paths, CameraPath, CameraPathFinished, CameraPathLoader,
+        CameraPathPlayback, Easing, PlayCameraPath,
+    },
+    input::Velocity,
+    mouse::CursorWorldPosition,
+};
+
+const LETTERBOX_Z: f32 = 950.0;
+
+#[derive(Component, Debug, Clone, Copy)]
+pub struct CameraTarget {
+    pub weight: f32,
+    pub priority: i32,
+}
+
+impl Default for CameraTarget {
+    fn default() -> Self {
+        Self {
+            weight: 1.0,
+            priority: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum LookaheadMode {
+    Cursor { fraction: f32, max_distance: f32 },
+    Velocity { seconds: f32, max_distance: f32 },
+}
+
+impl Default for LookaheadMode {
+    fn default() -> Self {
+        Self::Cursor {
+            fraction: 0.3,
+            max_distance: 160.0,
+        }
+    }
+}
+
+#[derive(Component, Debug)]
+pub struct CameraLookahead {
+    pub mode: LookaheadMode,
+    pub smoothing: f32,
+    offset: Vec2,
+}
+
+impl CameraLookahead {
+    pub fn new(mode: LookaheadMode) -> Self {
+        Self {
+            mode,
+            smoothing: 8.0,
+            offset: Vec2::ZERO,
+        }
+    }
+
+    /// `cursor_offset` is the cursor's offset from the center of the view. Using it rather
+    /// than the cursor's world position keeps the camera's own lookahead from feeding back in.
+    fn goal(&self, cursor_offset: Option<Vec2>, velocity: Option<Vec2>) -> Vec2 {
+        match self.mode {
+            LookaheadMode::Cursor {
+                fraction,
+                max_distance,
+            } => cursor_offset
+                .map(|offset| (offset * fraction).clamp_length_max(max_distance))
+                .unwrap_or(Vec2::ZERO),
+            LookaheadMode::Velocity {
+                seconds,
+                max_distance,
+            } => velocity
+                .map(|velocity| (velocity * seconds).clamp_length_max(max_distance))
+                .unwrap_or(Vec2::ZERO),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CameraTransition {
+    from: Vec2,
+    from_zoom: f32,
+    timer: Timer,
+}
+
+#[derive(Component, Debug)]
+pub struct CameraController {
+    pub padding: f32,
+    pub min_zoom: f32,
+    pub max_zoom: f32,
+    pub transition_seconds: f32,
+    framed: Vec<Entity>,
+    position: Vec2,
+    zoom: f32,
+    transition: Option<CameraTransition>,
+}
+
+impl Default for CameraController {
+    fn default() -> Self {
+        Self {
+            padding: 128.0,
+            min_zoom: 1.0,
+            max_zoom: 3.0,
+            transition_seconds: 0.75,
+            framed: Vec::new(),
+            position: Vec2::ZERO,
+            zoom: 1.0,
+            transition: None,
+        }
+    }
+}
+
+impl CameraController {
+    pub fn start_transition(&mut self) {
+        self.start_transition_from(self.position, self.zoom);
+    }
+
+    /// Position and zoom before pixel-perfect snapping, in the space camera paths use.
+    pub(crate) fn view(&self) -> (Vec2, f32) {
+        (self.position, self.zoom)
+    }
+
+    pub fn start_transition_from(&mut self, position: Vec2, zoom: f32) {
+        self.transition = Some(CameraTransition {
+            from: position,
+            from_zoom: zoom,
+            timer: Timer::from_seconds(self.transition_seconds, false),
+        });
+    }
+
+    fn update(&mut self, time: &Time, goal: Vec2, goal_zoom: f32) {
+        let (position, zoom) = match self.transition.as_mut() {
+            Some(transition) => {
+                transition.timer.tick(time.delta());
+                let t = Easing::EaseInOut.apply(transition.timer.percent());
+                (
+                    transition.from.lerp(goal, t),
+                    transition.from_zoom + (goal_zoom - transition.from_zoom) * t,
+                )
+            }
+            None => (goal, goal_zoom),
+        };
+
+        if let Some(true) = self.transition.as_ref().map(|t| t.timer.finished()) {
+            self.transition = None;
+        }
+
+        self.position = position;
+        self.zoom = zoom;
+    }
+}
+
+/// Renders at an integer multiple of `virtual_resolution`. The largest multiple that fits the
+/// window is shown and black bars cover the rest.
+#[derive(Debug, Clone, Copy)]
+pub struct PixelPerfect {
+    pub virtual_resolution: Vec2,
+    pub snap_sprites: bool,
+}
+
+impl PixelPerfect {
+    pub fn new(width: f32, height: f32) -> Self {
+        Self {
+            virtual_resolution: Vec2::new(width, height),
+            snap_sprites: false,
+        }
+    }
+
+    pub fn with_sprite_snapping(mut self) -> Self {
+        self.snap_sprites = true;
+        self
+    }
+
+    fn fit(&self, window_size: Vec2) -> f32 {
+        (window_size / self.virtual_resolution)
+            .min_element()
+            .floor()
+            .max(1.0)
+    }
+
+    fn snap(&self, position: Vec2, zoom: f32, window_size: Vec2) -> (Vec2, f32) {
+        let pixels_per_texel = (self.fit(window_size) / zoom).round().max(1.0);
+
+        (position.round(), 1.0 / pixels_per_texel)
+    }
+
+    /// Size in window pixels of the area left uncovered by the letterbox.
+    fn view_size(&self, window_size: Vec2) -> Vec2 {
+        self.virtual_resolution * self.fit(window_size)
+    }
+}
+
+#[derive(Component, Debug, Clone, Copy)]
+struct LetterboxBar {
+    side: Vec2,
+}
+
+impl LetterboxBar {
+    /// Offset from the window's center and size of the bar, both in window pixels.
+    fn rect(&self, view_size: Vec2, window_size: Vec2) -> (Vec2, Vec2) {
+        let size = if self.side.x != 0.0 {
+            Vec2::new((window_size.x - view_size.x) / 2.0, window_size.y)
+        } else {
+            Vec2::new(window_size.x, (window_size.y - view_size.y) / 2.0)
+        };
+        let size = size.max(Vec2::ZERO);
+        (self.side * (view_size + size) / 2.0, size)
+    }
+}
+
+fn spawn_letterbox(mut commands: Commands) {
+    for side in [Vec2::X, -Vec2::X, Vec2::Y, -Vec2::Y] {
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: Color::BLACK,
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert(LetterboxBar { side });
+    }
+}
+
+fn despawn_letterbox(mut commands: Commands, query: Query<Entity, With<LetterboxBar>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+fn update_letterbox(
+    windows: Res<Windows>,
+    pixel_perfect: Res<PixelPerfect>,
+    cameras: Query<
+        (&Camera, &Transform, &OrthographicProjection),
+        (With<CameraController>, Without<LetterboxBar>),
+    >,
+    mut bars: Query<(&LetterboxBar, &mut Transform, &mut Sprite)>,
+) {
+    let (camera, camera_transform, projection) = match cameras.iter().next() {
+        Some(camera) => camera,
+        None => return,
+    };
+    let window_size = camera_window_size(camera, &windows);
+    let view_size = pixel_perfect.view_size(window_size);
+
+    for (bar, mut transform, mut sprite) in bars.iter_mut() {
+        let (offset, size) = bar.rect(view_size, window_size);
+        let position = camera_transform.translation.truncate() + offset * projection.scale;
+        transform.translation = position.extend(LETTERBOX_Z);
+        sprite.custom_size = Some(size * projection.scale);
+    }
+}
+
+pub(crate) fn camera_window_size(camera: &Camera, windows: &Windows) -> Vec2 {
+    let window = if let RenderTarget::Window(id) = camera.target {
+        windows.get(id)
+    } else {
+        windows.get_primary()
+    };
+    window
+        .map(|window| Vec2::new(window.width(), window.height()))
+        .unwrap_or(Vec2::ONE)
+}
+
+/// Writes an unsnapped `position` and `zoom` to the camera, snapped if pixel-perfect is on.
+pub(crate) fn apply_view(
+    pixel_perfect: Option<&PixelPerfect>,
+    (position, zoom): (Vec2, f32),
+    window_size: Vec2,
+    transform: &mut Transform,
+    projection: &mut OrthographicProjection,
+) {
+    let (position, scale) = match pixel_perfect {
+        Some(pixel_perfect) => pixel_perfect.snap(position, zoom, window_size),
+        None => (position, zoom),
+    };
+
+    transform.translation.x = position.x;
+    transform.translation.y = position.y;
+    if projection.scale != scale {
+        projection.scale = scale;
+    }
+}
+
+struct Framing {
+    center: Vec2,
+    size: Vec2,
+    /// Sorted so the set can be compared between frames.
+    entities: Vec<Entity>,
+}
+
+fn frame_targets(targets: &[(Entity, Vec2, CameraTarget)]) -> Option<Framing> {
+    let priority = targets.iter().map(|(_, _, target)| target.priority).max()?;
+    let framed = targets
+        .iter()
+        .filter(|(_, _, target)| target.priority == priority)
+        .map(|(_, position, target)| (*position, *target))
+        .collect::<Vec<_>>();
+    let mut entities = targets
+        .iter()
+        .filter(|(_, _, target)| target.priority == priority)
+        .map(|(entity, _, _)| *entity)
+        .collect::<Vec<_>>();
+    entities.sort();
+
+    let total_weight: f32 = framed
+        .iter()
+        .map(|(_, target)| target.weight.max(0.0))
+        .sum();
+    let center = if total_weight > 0.0 {
+        framed.iter().fold(Vec2::ZERO, |sum, (position, target)| {
+            sum + *position * target.weight.max(0.0)
+        }) / total_weight
+    } else {
+        framed
+            .iter()
+            .fold(Vec2::ZERO, |sum, (position, _)| sum + *position)
+            / framed.len() as f32
+    };
+
+    let half_size = framed
+        .iter()
+        .map(|(position, _)| (*position - center).abs())
+        .fold(Vec2::ZERO, Vec2::max);
+
+    Some(Framing {
+        center,
+        size: half_size * 2.0,
+        entities,
+    })
+}
+
+fn camera_lookahead(
+    time: Res<Time>,
+    cursor: Res<CursorWorldPosition>,
+    mut query: Query<(Option<&Velocity>, &mut CameraLookahead)>,
+) {
+    for (velocity, mut lookahead) in query.iter_mut() {
+        let velocity = velocity.map(|velocity| velocity.0);
+        let goal = lookahead.goal(cursor.view_offset(), velocity);
+        let blend = 1.0 - (-lookahead.smoothing * time.delta_seconds()).exp();
+        lookahead.offset = lookahead.offset.lerp(goal, blend);
+    }
+}
 
 fn camera_movement(
+    time: Res<Time>,
+    windows: Res<Windows>,
+    pixel_perfect: Option<Res<PixelPerfect>>,
     mut transforms: ParamSet<(
-        Query<&mut Transform, With<Camera>>,
-        Query<&Transform, With<Player>>,
+        Query<
+            (
+                &Camera,
+                &mut Transform,
+                &mut OrthographicProjection,
+                &mut CameraController,
+            ),
+            Without<CameraPathPlayback>,
+        >,
+        Query<(Entity, &Transform, &CameraTarget, Option<&CameraLookahead>)>,
     )>,
-    mut player_position: Local<Vec2>,
 ) {
-    for player_transform in transforms.p1().iter() {
-        *player_position = Vec2::new(
-            player_transform.translation.x,
-            player_transform.translation.y,
+    let targets = transforms
+        .p1()
+        .iter()
+        .map(|(entity, transform, target, lookahead)| {
+            let offset = lookahead
+                .map(|lookahead| lookahead.offset)
+                .unwrap_or(Vec2::ZERO);
+            (entity, transform.translation.truncate() + offset, *target)
+        })
+        .collect::<Vec<_>>();
+
+    let framing = match frame_targets(&targets) {
+        Some(framing) => framing,
+        None => return,
+    };
+
+    for (camera, mut camera_transform, mut projection, mut controller) in transforms.p0().iter_mut()
+    {
+        let window_size = camera_window_size(camera, &windows);
+
+        let wanted = (framing.size + Vec2::splat(controller.padding * 2.0)) / window_size;
+        let goal_zoom = wanted
+            .x
+            .max(wanted.y)
+            .clamp(controller.min_zoom, controller.max_zoom);
+
+        // Ease whenever the framed set changes, not only when the priority does,
+        // so a target joining at the same priority doesn't cut the framing.
+        if !controller.framed.is_empty() && controller.framed != framing.entities {
+            controller.start_transition();
+        }
+        controller.framed = framing.entities.clone();
+        controller.update(&time, framing.center, goal_zoom);
+
+        apply_view(
+            pixel_perfect.as_deref(),
+            controller.view(),
+            window_size,
+            &mut camera_transform,
+            &mut projection,
         );
     }
+}
 
-    for mut camera_transform in transforms.p0().iter_mut() {
-        *camera_transform = Transform::from_xyz(player_position.x, player_position.y, 0.0);
+fn snap_sprites(
+    mut query: Query<&mut GlobalTransform, Or<(With<Sprite>, With<TextureAtlasSprite>)>>,
+) {
+    for mut transform in query.iter_mut() {
+        transform.translation.x = transform.translation.x.round();
+        transform.translation.y = transform.translation.y.round();
     }
 }
 
@@ -32,16 +436,105 @@ pub trait CameraState: Debug + Clone + Copy + PartialEq + Eq + Hash + Sync + Sen
 #[derive(Default)]
 pub struct CameraPlugin<T: CameraState> {
     state: T,
+    pixel_perfect: Option<PixelPerfect>,
 }
 
 impl<T: 'static + CameraState> CameraPlugin<T> {
     pub fn new(state: T) -> Self {
-        Self { state }
+        Self {
+            state,
+            pixel_perfect: None,
+        }
+    }
+
+    pub fn with_pixel_perfect(mut self, pixel_perfect: PixelPerfect) -> Self {
+        self.pixel_perfect = Some(pixel_perfect);
+        self
     }
 }
 
 impl<T: 'static + CameraState> Plugin for CameraPlugin<T> {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.add_system(camera_movement.run_in_state(self.state));
+        app.add_asset::<CameraPath>()
+            .init_asset_loader::<CameraPathLoader>()
+            .add_event::<PlayCameraPath>()
+            .add_event::<CameraPathFinished>()
+            .add_system(
+                start_camera_paths
+                    .run_in_state(self.state)
+                    .before("play_camera_paths"),
+            )
+            .add_system(
+                play_camera_paths
+                    .run_in_state(self.state)
+                    .label("play_camera_paths")
+                    .before("camera_movement"),
+            )
+            .add_system(
+                camera_lookahead
+                    .run_in_state(self.state)
+                    .after("interpolate_transforms")
+                    .before("camera_movement"),
+            )
+            .add_system(
+                camera_movement
+                    .run_in_state(self.state)
+                    .label("camera_movement"),
+            );
+
+        if let Some(pixel_perfect) = self.pixel_perfect {
+            app.insert_resource(pixel_perfect)
+                .add_enter_system(self.state, spawn_letterbox)
+                .add_exit_system(self.state, despawn_letterbox)
+                .add_system(
+                    update_letterbox
+                        .run_in_state(self.state)
+                        .after("camera_movement")
+                        .after("play_camera_paths"),
+                );
+
+            if pixel_perfect.snap_sprites {
+                app.add_system_to_stage(
+                    CoreStage::PostUpdate,
+                    snap_sprites
+                        .run_in_state(self.state)
+                        .after(TransformSystem::TransformPropagate),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn letterbox_covers_what_the_integer_scale_leaves() {
+        let pixel_perfect = PixelPerfect::new(640.0, 360.0);
+        let window_size = Vec2::new(1600.0, 900.0);
+
+        let view_size = pixel_perfect.view_size(window_size);
+        assert_eq!(view_size, Vec2::new(1280.0, 720.0));
+
+        let (offset, size) = LetterboxBar { side: Vec2::X }.rect(view_size, window_size);
+        assert_eq!(size, Vec2::new(160.0, 900.0));
+        assert_eq!(offset, Vec2::new(720.0, 0.0));
+
+        let (offset, size) = LetterboxBar { side: -Vec2::Y }.rect(view_size, window_size);
+        assert_eq!(size, Vec2::new(1600.0, 90.0));
+        assert_eq!(offset, Vec2::new(0.0, -405.0));
+    }
+
+    #[test]
+    fn small_windows_have_no_letterbox() {
+        let pixel_perfect = PixelPerfect::new(640.0, 360.0);
+        let window_size = Vec2::new(500.0, 300.0);
+
+        let view_size = pixel_perfect.view_size(window_size);
+        for side in [Vec2::X, Vec2::Y] {
+            let (_, size) = LetterboxBar { side }.rect(view_size, window_size);
+            assert_eq!(size.min_element(), 0.0);
+        }
     }
 }
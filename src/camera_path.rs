@@ -0,0 +1,328 @@
+use bevy::{
+    asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset},
+    core::Time,
+    ecs::component::Component,
+    math::Vec2,
+    prelude::{
+        Assets, Camera, Commands, Entity, EventReader, EventWriter, Handle, OrthographicProjection,
+        Query, Res, Transform, With,
+    },
+    reflect::TypeUuid,
+    window::Windows,
+};
+use serde::Deserialize;
+
+use crate::camera::{apply_view, camera_window_size, CameraController, PixelPerfect};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Default for Easing {
+    fn default() -> Self {
+        Self::EaseInOut
+    }
+}
+
+impl Easing {
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+fn default_duration() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct CameraKeyframe {
+    pub position: Vec2,
+    pub zoom: f32,
+    /// Seconds to move here from the previous keyframe.
+    #[serde(default = "default_duration")]
+    pub duration: f32,
+    #[serde(default)]
+    pub easing: Easing,
+    /// Seconds to stay here before moving on.
+    #[serde(default)]
+    pub hold: f32,
+}
+
+/// Loaded from `*.camera.ron` files.
+#[derive(Debug, Clone, Default, Deserialize, TypeUuid)]
+#[uuid = "4f6a0b0e-2a4f-4d0c-9a53-6c2b1b8e7d31"]
+pub struct CameraPath {
+    keyframes: Vec<CameraKeyframe>,
+}
+
+impl CameraPath {
+    /// Position and zoom `elapsed` seconds into the move toward keyframe `index`.
+    fn sample(&self, index: usize, elapsed: f32, from: (Vec2, f32)) -> Option<(Vec2, f32)> {
+        let keyframe = self.keyframes.get(index)?;
+        let t = if keyframe.duration > 0.0 {
+            keyframe.easing.apply(elapsed / keyframe.duration)
+        } else {
+            1.0
+        };
+        Some((
+            from.0.lerp(keyframe.position, t),
+            from.1 + (keyframe.zoom - from.1) * t,
+        ))
+    }
+}
+
+#[derive(Default)]
+pub struct CameraPathLoader;
+
+impl AssetLoader for CameraPathLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let path = ron::de::from_bytes::<CameraPath>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(path));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["camera.ron"]
+    }
+}
+
+#[derive(Component, Debug)]
+pub struct CameraPathPlayback {
+    path: Handle<CameraPath>,
+    keyframe: usize,
+    elapsed: f32,
+    from: Option<(Vec2, f32)>,
+}
+
+impl CameraPathPlayback {
+    pub fn new(path: Handle<CameraPath>) -> Self {
+        Self {
+            path,
+            keyframe: 0,
+            elapsed: 0.0,
+            from: None,
+        }
+    }
+
+    /// Moves `delta` seconds along `path`, starting from `start` the first time. Returns the
+    /// camera's position and zoom, and whether the path has finished.
+    fn advance(
+        &mut self,
+        path: &CameraPath,
+        start: (Vec2, f32),
+        delta: f32,
+    ) -> ((Vec2, f32), bool) {
+        let mut from = self.from.unwrap_or(start);
+
+        self.elapsed += delta;
+        while let Some(keyframe) = path.keyframes.get(self.keyframe) {
+            let length = keyframe.duration + keyframe.hold;
+            if self.elapsed < length {
+                break;
+            }
+            self.elapsed -= length;
+            self.keyframe += 1;
+            from = (keyframe.position, keyframe.zoom);
+        }
+        self.from = Some(from);
+
+        match path.sample(self.keyframe, self.elapsed, from) {
+            Some(view) => (view, false),
+            None => (from, true),
+        }
+    }
+}
+
+/// Send to take every following camera away from `camera_movement` and play `path` on it.
+#[derive(Debug, Clone)]
+pub struct PlayCameraPath {
+    pub path: Handle<CameraPath>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CameraPathFinished {
+    pub camera: Entity,
+    pub path: Handle<CameraPath>,
+}
+
+pub fn start_camera_paths(
+    mut commands: Commands,
+    mut events: EventReader<PlayCameraPath>,
+    cameras: Query<Entity, With<CameraController>>,
+) {
+    for event in events.iter() {
+        for camera in cameras.iter() {
+            commands
+                .entity(camera)
+                .insert(CameraPathPlayback::new(event.path.clone()));
+        }
+    }
+}
+
+pub fn play_camera_paths(
+    mut commands: Commands,
+    time: Res<Time>,
+    windows: Res<Windows>,
+    pixel_perfect: Option<Res<PixelPerfect>>,
+    paths: Res<Assets<CameraPath>>,
+    mut finished: EventWriter<CameraPathFinished>,
+    mut query: Query<(
+        Entity,
+        &Camera,
+        &mut CameraPathPlayback,
+        &mut Transform,
+        &mut OrthographicProjection,
+        Option<&mut CameraController>,
+    )>,
+) {
+    for (entity, camera, mut playback, mut transform, mut projection, controller) in
+        query.iter_mut()
+    {
+        let path = match paths.get(&playback.path) {
+            Some(path) => path,
+            None => continue,
+        };
+
+        // Paths run in the controller's unsnapped space so the handoff back doesn't pop.
+        let start = match controller.as_deref() {
+            Some(controller) => controller.view(),
+            None => (transform.translation.truncate(), projection.scale),
+        };
+        let ((position, zoom), done) = playback.advance(path, start, time.delta_seconds());
+        if done {
+            if let Some(mut controller) = controller {
+                controller.start_transition_from(position, zoom);
+            }
+            commands.entity(entity).remove::<CameraPathPlayback>();
+            finished.send(CameraPathFinished {
+                camera: entity,
+                path: playback.path.clone(),
+            });
+        }
+
+        apply_view(
+            pixel_perfect.as_deref(),
+            (position, zoom),
+            camera_window_size(camera, &windows),
+            &mut transform,
+            &mut projection,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EASINGS: [Easing; 4] = [
+        Easing::Linear,
+        Easing::EaseIn,
+        Easing::EaseOut,
+        Easing::EaseInOut,
+    ];
+
+    fn keyframe(x: f32, zoom: f32, duration: f32, hold: f32) -> CameraKeyframe {
+        CameraKeyframe {
+            position: Vec2::new(x, 0.0),
+            zoom,
+            duration,
+            easing: Easing::Linear,
+            hold,
+        }
+    }
+
+    fn path() -> CameraPath {
+        CameraPath {
+            keyframes: vec![
+                keyframe(100.0, 2.0, 1.0, 0.5),
+                keyframe(200.0, 1.0, 2.0, 0.0),
+            ],
+        }
+    }
+
+    #[test]
+    fn easing_hits_segment_ends() {
+        for easing in EASINGS {
+            assert_eq!(easing.apply(0.0), 0.0, "{:?}", easing);
+            assert_eq!(easing.apply(1.0), 1.0, "{:?}", easing);
+            assert_eq!(easing.apply(-1.0), 0.0, "{:?}", easing);
+            assert_eq!(easing.apply(2.0), 1.0, "{:?}", easing);
+        }
+        assert_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+    }
+
+    #[test]
+    fn starts_from_the_camera() {
+        let mut playback = CameraPathPlayback::new(Handle::default());
+        let (view, done) = playback.advance(&path(), (Vec2::ZERO, 1.0), 0.0);
+        assert_eq!(view, (Vec2::ZERO, 1.0));
+        assert!(!done);
+    }
+
+    #[test]
+    fn reaches_and_holds_each_keyframe() {
+        let path = path();
+        let mut playback = CameraPathPlayback::new(Handle::default());
+
+        let (view, _) = playback.advance(&path, (Vec2::ZERO, 1.0), 0.5);
+        assert_eq!(view, (Vec2::new(50.0, 0.0), 1.5));
+
+        let (view, _) = playback.advance(&path, (Vec2::ZERO, 1.0), 0.5);
+        assert_eq!(view, (Vec2::new(100.0, 0.0), 2.0));
+
+        let (view, done) = playback.advance(&path, (Vec2::ZERO, 1.0), 0.25);
+        assert_eq!(view, (Vec2::new(100.0, 0.0), 2.0));
+        assert!(!done);
+    }
+
+    #[test]
+    fn next_segment_starts_from_the_previous_keyframe() {
+        let path = path();
+        let mut playback = CameraPathPlayback::new(Handle::default());
+
+        let (view, _) = playback.advance(&path, (Vec2::ZERO, 1.0), 1.5);
+        assert_eq!(view, (Vec2::new(100.0, 0.0), 2.0));
+
+        let (view, _) = playback.advance(&path, (Vec2::ZERO, 1.0), 1.0);
+        assert_eq!(view, (Vec2::new(150.0, 0.0), 1.5));
+    }
+
+    #[test]
+    fn finishes_on_the_last_keyframe() {
+        let mut playback = CameraPathPlayback::new(Handle::default());
+        let (view, done) = playback.advance(&path(), (Vec2::ZERO, 1.0), 10.0);
+        assert_eq!(view, (Vec2::new(200.0, 0.0), 1.0));
+        assert!(done);
+    }
+
+    #[test]
+    fn parses_ron_with_defaults() {
+        let path = ron::from_str::<CameraPath>(
+            "(keyframes: [(position: (10.0, 20.0), zoom: 2.0), \
+             (position: (0.0, 0.0), zoom: 1.0, duration: 3.0, easing: EaseIn, hold: 0.5)])",
+        )
+        .unwrap();
+
+        assert_eq!(path.keyframes[0].position, Vec2::new(10.0, 20.0));
+        assert_eq!(path.keyframes[0].duration, 1.0);
+        assert_eq!(path.keyframes[0].easing, Easing::EaseInOut);
+        assert_eq!(path.keyframes[1].easing, Easing::EaseIn);
+        assert_eq!(path.keyframes[1].hold, 0.5);
+    }
+}
@@ -0,0 +1,370 @@
+use std::cmp::Ordering;
+
+use bevy::{math::Vec2, prelude::Component};
+
+const MAX_ITERATIONS: usize = 4;
+
+/// Collision shape centered on the entity's translation. Entities with a `Velocity` are moved
+/// with [`move_and_slide`] against static colliders, tilemaps and each other; moving bodies are
+/// resolved one after another, so a body only blocks others and is never pushed by them.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub enum Collider {
+    Aabb { half_extents: Vec2 },
+    Circle { radius: f32 },
+}
+
+impl Collider {
+    pub fn aabb(width: f32, height: f32) -> Self {
+        Self::Aabb {
+            half_extents: Vec2::new(width, height) * 0.5,
+        }
+    }
+
+    pub fn circle(radius: f32) -> Self {
+        Self::Circle { radius }
+    }
+
+    pub fn half_extents(&self) -> Vec2 {
+        match *self {
+            Collider::Aabb { half_extents } => half_extents,
+            Collider::Circle { radius } => Vec2::splat(radius),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Contact {
+    /// Points out of the other shape, along the shortest way to separate them.
+    pub normal: Vec2,
+    pub depth: f32,
+}
+
+pub fn contact(a: &Collider, a_position: Vec2, b: &Collider, b_position: Vec2) -> Option<Contact> {
+    let offset = a_position - b_position;
+    match (*a, *b) {
+        (Collider::Aabb { half_extents: a }, Collider::Aabb { half_extents: b }) => {
+            let overlap = a + b - offset.abs();
+            if overlap.x <= 0.0 || overlap.y <= 0.0 {
+                None
+            } else if overlap.x < overlap.y {
+                Some(Contact {
+                    normal: Vec2::new(sign(offset.x), 0.0),
+                    depth: overlap.x,
+                })
+            } else {
+                Some(Contact {
+                    normal: Vec2::new(0.0, sign(offset.y)),
+                    depth: overlap.y,
+                })
+            }
+        }
+        (Collider::Circle { radius }, Collider::Aabb { half_extents }) => {
+            circle_aabb(offset, radius, half_extents)
+        }
+        (Collider::Aabb { half_extents }, Collider::Circle { radius }) => {
+            circle_aabb(-offset, radius, half_extents).map(|contact| Contact {
+                normal: -contact.normal,
+                depth: contact.depth,
+            })
+        }
+        (Collider::Circle { radius: a }, Collider::Circle { radius: b }) => {
+            let distance = offset.length();
+            let radii = a + b;
+            if distance >= radii {
+                None
+            } else {
+                Some(Contact {
+                    normal: if distance > 0.0 {
+                        offset / distance
+                    } else {
+                        Vec2::X
+                    },
+                    depth: radii - distance,
+                })
+            }
+        }
+    }
+}
+
+fn circle_aabb(offset: Vec2, radius: f32, half_extents: Vec2) -> Option<Contact> {
+    let closest = offset.clamp(-half_extents, half_extents);
+    if closest == offset {
+        let overlap = half_extents - offset.abs();
+        return if overlap.x < overlap.y {
+            Some(Contact {
+                normal: Vec2::new(sign(offset.x), 0.0),
+                depth: overlap.x + radius,
+            })
+        } else {
+            Some(Contact {
+                normal: Vec2::new(0.0, sign(offset.y)),
+                depth: overlap.y + radius,
+            })
+        };
+    }
+
+    let difference = offset - closest;
+    let distance = difference.length();
+    if distance >= radius {
+        None
+    } else {
+        Some(Contact {
+            normal: difference / distance,
+            depth: radius - distance,
+        })
+    }
+}
+
+fn sign(value: f32) -> f32 {
+    if value < 0.0 {
+        -1.0
+    } else {
+        1.0
+    }
+}
+
+/// Grid of solid tiles. Tile `(0, 0)` has its lower left corner at the entity's translation.
+#[derive(Component, Debug, Clone)]
+pub struct TileMap {
+    pub width: usize,
+    pub height: usize,
+    pub tile_size: Vec2,
+    solid: Vec<bool>,
+}
+
+impl TileMap {
+    pub fn new(width: usize, height: usize, tile_size: Vec2) -> Self {
+        Self {
+            width,
+            height,
+            tile_size,
+            solid: vec![false; width * height],
+        }
+    }
+
+    /// Builds a map from rows of text, top row first, where `#` marks a solid tile.
+    pub fn from_rows(rows: &[&str], tile_size: Vec2) -> Self {
+        let height = rows.len();
+        let width = rows
+            .iter()
+            .map(|row| row.chars().count())
+            .max()
+            .unwrap_or(0);
+        let mut map = Self::new(width, height, tile_size);
+        for (row, line) in rows.iter().enumerate() {
+            for (x, tile) in line.chars().enumerate() {
+                map.set_solid(x, height - 1 - row, tile == '#');
+            }
+        }
+        map
+    }
+
+    pub fn set_solid(&mut self, x: usize, y: usize, solid: bool) -> &mut Self {
+        if x < self.width && y < self.height {
+            self.solid[y * self.width + x] = solid;
+        }
+        self
+    }
+
+    pub fn is_solid(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.solid[y * self.width + x]
+    }
+
+    /// Tile containing `point`, for a map whose lower left corner is at `origin`.
+    pub fn tile_at(&self, origin: Vec2, point: Vec2) -> Option<(usize, usize)> {
+        let tile = ((point - origin) / self.tile_size).floor();
+        if tile.x < 0.0 || tile.y < 0.0 {
+            return None;
+        }
+        let (x, y) = (tile.x as usize, tile.y as usize);
+        if x < self.width && y < self.height {
+            Some((x, y))
+        } else {
+            None
+        }
+    }
+
+    pub fn tile_center(&self, origin: Vec2, x: usize, y: usize) -> Vec2 {
+        origin + (Vec2::new(x as f32, y as f32) + 0.5) * self.tile_size
+    }
+
+    /// Colliders for the solid tiles touching the box from `min` to `max`, relative to `origin`.
+    pub fn colliders_in(
+        &self,
+        origin: Vec2,
+        min: Vec2,
+        max: Vec2,
+    ) -> impl Iterator<Item = (Vec2, Collider)> + '_ {
+        let collider = Collider::aabb(self.tile_size.x, self.tile_size.y);
+        let first = ((min - origin) / self.tile_size).floor().max(Vec2::ZERO);
+        let end = (((max - origin) / self.tile_size).floor() + 1.0)
+            .clamp(Vec2::ZERO, Vec2::new(self.width as f32, self.height as f32));
+        let columns = first.x as usize..end.x as usize;
+        let rows = first.y as usize..end.y as usize;
+
+        rows.flat_map(move |y| columns.clone().map(move |x| (x, y)))
+            .filter(move |(x, y)| self.is_solid(*x, *y))
+            .map(move |(x, y)| (self.tile_center(origin, x, y), collider))
+    }
+}
+
+/// Moves a shape by `velocity * delta`, pushing it out of `obstacles` and removing
+/// the part of its velocity that points into them so it slides along walls.
+pub fn move_and_slide(
+    collider: &Collider,
+    position: Vec2,
+    velocity: Vec2,
+    delta: f32,
+    obstacles: &[(Vec2, Collider)],
+) -> (Vec2, Vec2) {
+    let mut position = position;
+    let mut velocity = velocity;
+
+    // Step no further than the shape's half extent at a time so fast movers can't tunnel.
+    let motion = velocity * delta;
+    let reach = collider.half_extents().min_element().max(f32::EPSILON);
+    let steps = (motion.length() / reach).ceil().max(1.0) as usize;
+    let mut step = motion / steps as f32;
+
+    for _ in 0..steps {
+        position += step;
+        for _ in 0..MAX_ITERATIONS {
+            let deepest = obstacles
+                .iter()
+                .filter_map(|(obstacle_position, obstacle)| {
+                    contact(collider, position, obstacle, *obstacle_position)
+                })
+                .max_by(|a, b| a.depth.partial_cmp(&b.depth).unwrap_or(Ordering::Equal));
+
+            let contact = match deepest {
+                Some(contact) => contact,
+                None => break,
+            };
+            position += contact.normal * contact.depth;
+            velocity -= contact.normal * velocity.dot(contact.normal).min(0.0);
+            step -= contact.normal * step.dot(contact.normal).min(0.0);
+        }
+    }
+
+    (position, velocity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-3;
+
+    fn wall(x: f32) -> (Vec2, Collider) {
+        (Vec2::new(x, 0.0), Collider::aabb(10.0, 200.0))
+    }
+
+    #[test]
+    fn stops_at_a_wall() {
+        let (position, velocity) = move_and_slide(
+            &Collider::circle(5.0),
+            Vec2::ZERO,
+            Vec2::new(100.0, 0.0),
+            1.0,
+            &[wall(20.0)],
+        );
+
+        assert!((position.x - 10.0).abs() < EPSILON, "{}", position);
+        assert!(velocity.x.abs() < EPSILON, "{}", velocity);
+    }
+
+    #[test]
+    fn slides_along_a_wall() {
+        let (position, velocity) = move_and_slide(
+            &Collider::circle(5.0),
+            Vec2::ZERO,
+            Vec2::new(100.0, 50.0),
+            1.0,
+            &[wall(20.0)],
+        );
+
+        assert!((position.x - 10.0).abs() < EPSILON, "{}", position);
+        assert!((position.y - 50.0).abs() < EPSILON, "{}", position);
+        assert!(velocity.x.abs() < EPSILON, "{}", velocity);
+        assert!((velocity.y - 50.0).abs() < EPSILON, "{}", velocity);
+    }
+
+    #[test]
+    fn stops_in_an_inside_corner() {
+        let map = TileMap::from_rows(&["###", "..#", "..#"], Vec2::splat(10.0));
+        let obstacles = map
+            .colliders_in(Vec2::ZERO, Vec2::ZERO, Vec2::splat(30.0))
+            .collect::<Vec<_>>();
+        assert_eq!(obstacles.len(), 5);
+
+        let (position, velocity) = move_and_slide(
+            &Collider::circle(4.0),
+            Vec2::splat(5.0),
+            Vec2::splat(100.0),
+            1.0,
+            &obstacles,
+        );
+
+        assert!(
+            position.abs_diff_eq(Vec2::splat(16.0), EPSILON),
+            "{}",
+            position
+        );
+        assert!(velocity.abs_diff_eq(Vec2::ZERO, EPSILON), "{}", velocity);
+    }
+
+    #[test]
+    fn fast_moves_do_not_tunnel_through_thin_tiles() {
+        let mut map = TileMap::new(1, 1, Vec2::new(2.0, 40.0));
+        map.set_solid(0, 0, true);
+        let origin = Vec2::new(49.0, -20.0);
+        let obstacles = map
+            .colliders_in(origin, Vec2::splat(-200.0), Vec2::splat(200.0))
+            .collect::<Vec<_>>();
+
+        let (position, _) = move_and_slide(
+            &Collider::circle(4.0),
+            Vec2::ZERO,
+            Vec2::new(10_000.0, 0.0),
+            1.0 / 60.0,
+            &obstacles,
+        );
+
+        assert!((position.x - 45.0).abs() < EPSILON, "{}", position);
+    }
+
+    #[test]
+    fn circle_against_aabb() {
+        let circle = Collider::circle(5.0);
+        let aabb = Collider::aabb(20.0, 20.0);
+
+        let hit = contact(&circle, Vec2::new(12.0, 0.0), &aabb, Vec2::ZERO).unwrap();
+        assert!(hit.normal.abs_diff_eq(Vec2::X, EPSILON));
+        assert!((hit.depth - 3.0).abs() < EPSILON);
+
+        let hit = contact(&aabb, Vec2::ZERO, &circle, Vec2::new(12.0, 0.0)).unwrap();
+        assert!(hit.normal.abs_diff_eq(-Vec2::X, EPSILON));
+        assert!((hit.depth - 3.0).abs() < EPSILON);
+
+        // Past the corner the closest point is the corner itself.
+        assert!(contact(&circle, Vec2::new(14.0, 14.0), &aabb, Vec2::ZERO).is_none());
+        let hit = contact(&circle, Vec2::new(13.0, 13.0), &aabb, Vec2::ZERO).unwrap();
+        assert!(hit.normal.abs_diff_eq(Vec2::ONE.normalize(), EPSILON));
+
+        // A center inside the box is pushed out along the shortest axis.
+        let hit = contact(&circle, Vec2::new(2.0, 8.0), &aabb, Vec2::ZERO).unwrap();
+        assert!(hit.normal.abs_diff_eq(Vec2::Y, EPSILON));
+        assert!((hit.depth - 7.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn colliders_in_only_returns_nearby_solid_tiles() {
+        let map = TileMap::from_rows(&["#..#", "....", "#..#"], Vec2::splat(10.0));
+        let near_origin = map
+            .colliders_in(Vec2::ZERO, Vec2::ZERO, Vec2::splat(5.0))
+            .map(|(position, _)| position)
+            .collect::<Vec<_>>();
+
+        assert_eq!(near_origin, vec![Vec2::splat(5.0)]);
+    }
+}
@@ -0,0 +1,98 @@
+use std::{fmt::Debug, hash::Hash};
+
+use bevy::{
+    hierarchy::DespawnRecursiveExt,
+    prelude::{
+        App, Commands, Component, Entity, EventReader, EventWriter,
+        ParallelSystemDescriptorCoercion, Plugin, Query,
+    },
+};
+use iyes_loopless::prelude::IntoConditionalSystem;
+
+use crate::{dash::Invulnerable, simulation::FIXED_UPDATE};
+
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Health {
+    pub max: f32,
+    current: f32,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self { max, current: max }
+    }
+
+    pub fn current(&self) -> f32 {
+        self.current
+    }
+
+    pub fn dead(&self) -> bool {
+        self.current <= 0.0
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Damage {
+    pub target: Entity,
+    pub amount: f32,
+    pub source: Option<Entity>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Died {
+    pub entity: Entity,
+    pub killer: Option<Entity>,
+}
+
+fn apply_damage(
+    mut commands: Commands,
+    mut damage: EventReader<Damage>,
+    mut died: EventWriter<Died>,
+    mut query: Query<(&mut Health, Option<&Invulnerable>)>,
+) {
+    for event in damage.iter() {
+        let (mut health, invulnerable) = match query.get_mut(event.target) {
+            Ok(target) => target,
+            Err(_) => continue,
+        };
+        if invulnerable.is_some() || health.dead() {
+            continue;
+        }
+
+        health.current = (health.current - event.amount).max(0.0);
+        if health.dead() {
+            commands.entity(event.target).despawn_recursive();
+            died.send(Died {
+                entity: event.target,
+                killer: event.source,
+            });
+        }
+    }
+}
+
+pub trait CombatState: Debug + Clone + Copy + PartialEq + Eq + Hash + Sync + Send {}
+
+#[derive(Default)]
+pub struct CombatPlugin<T: CombatState> {
+    state: T,
+}
+
+impl<T: 'static + CombatState> CombatPlugin<T> {
+    pub fn new(state: T) -> Self {
+        Self { state }
+    }
+}
+
+impl<T: 'static + CombatState> Plugin for CombatPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Damage>()
+            .add_event::<Died>()
+            .add_system_to_stage(
+                FIXED_UPDATE,
+                apply_damage
+                    .run_in_state(self.state)
+                    .label("apply_damage")
+                    .after("player_movement"),
+            );
+    }
+}
@@ -0,0 +1,175 @@
+use bevy::{
+    hierarchy::{BuildChildren, DespawnRecursiveExt},
+    math::{Vec2, Vec3},
+    prelude::{
+        Color, Commands, Component, Entity, Query, Res, ResMut, Sprite, SpriteBundle, Transform,
+        Visibility, With, Without,
+    },
+    transform::TransformBundle,
+    window::Windows,
+};
+
+use crate::{
+    action::InputSource,
+    camera::CameraController,
+    input::{Controlled, Player},
+    mouse::{AimSource, CursorWorldPosition},
+};
+
+const CROSSHAIR_Z: f32 = 900.0;
+
+#[derive(Debug, Clone, Copy)]
+pub struct CrosshairStyle {
+    pub color: Color,
+    pub snap_color: Color,
+    pub length: f32,
+    pub thickness: f32,
+    pub gap: f32,
+}
+
+impl Default for CrosshairStyle {
+    fn default() -> Self {
+        Self {
+            color: Color::WHITE,
+            snap_color: Color::rgb(1.0, 0.3, 0.3),
+            length: 6.0,
+            thickness: 2.0,
+            gap: 3.0,
+        }
+    }
+}
+
+/// Written by weapons and aim assist each frame to shape the crosshair.
+#[derive(Debug, Default)]
+pub struct CrosshairState {
+    /// Half-angle of the current weapon's spread in radians.
+    pub spread: f32,
+    /// World position the reticle should snap to instead of following the cursor.
+    pub snap: Option<Vec2>,
+}
+
+#[derive(Component, Debug)]
+pub struct Crosshair;
+
+#[derive(Component, Debug)]
+pub struct CrosshairArm {
+    direction: Vec2,
+}
+
+pub(crate) fn spawn_crosshair(mut commands: Commands, style: Res<CrosshairStyle>) {
+    let arm_size = |direction: Vec2| {
+        if direction.x == 0.0 {
+            Vec2::new(style.thickness, style.length)
+        } else {
+            Vec2::new(style.length, style.thickness)
+        }
+    };
+
+    commands
+        .spawn_bundle(TransformBundle::from_transform(Transform::from_xyz(
+            0.0,
+            0.0,
+            CROSSHAIR_Z,
+        )))
+        .insert(Crosshair)
+        .with_children(|parent| {
+            for direction in [Vec2::X, -Vec2::X, Vec2::Y, -Vec2::Y] {
+                parent
+                    .spawn_bundle(SpriteBundle {
+                        sprite: Sprite {
+                            color: style.color,
+                            custom_size: Some(arm_size(direction)),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    })
+                    .insert(CrosshairArm { direction });
+            }
+        });
+}
+
+pub(crate) fn despawn_crosshair(mut commands: Commands, query: Query<Entity, With<Crosshair>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub(crate) fn hide_os_cursor(mut windows: ResMut<Windows>) {
+    if let Some(window) = windows.get_primary_mut() {
+        window.set_cursor_visibility(false);
+    }
+}
+
+pub(crate) fn show_os_cursor(mut windows: ResMut<Windows>) {
+    if let Some(window) = windows.get_primary_mut() {
+        window.set_cursor_visibility(true);
+    }
+}
+
+/// Runs after the camera has moved this frame. [`CursorWorldPosition`] is resolved before the
+/// camera moves, so the reticle is placed from the cursor's offset in the view instead.
+pub(crate) fn update_crosshair(
+    cursor: Res<CursorWorldPosition>,
+    source: Res<AimSource>,
+    style: Res<CrosshairStyle>,
+    state: Res<CrosshairState>,
+    players: Query<
+        (&Transform, Option<&Controlled>),
+        (With<Player>, Without<Crosshair>, Without<CrosshairArm>),
+    >,
+    cameras: Query<
+        &Transform,
+        (
+            With<CameraController>,
+            Without<Crosshair>,
+            Without<CrosshairArm>,
+        ),
+    >,
+    mut crosshairs: Query<&mut Transform, (With<Crosshair>, Without<CrosshairArm>)>,
+    mut arms: Query<
+        (&CrosshairArm, &mut Transform, &mut Sprite, &mut Visibility),
+        Without<Crosshair>,
+    >,
+) {
+    let position = match (state.snap, *source) {
+        (Some(snap), _) => Some(snap),
+        (None, AimSource::Mouse) => cursor.view_offset().and_then(|offset| {
+            cameras
+                .iter()
+                .next()
+                .map(|camera| camera.mul_vec3(offset.extend(0.0)).truncate())
+        }),
+        (None, AimSource::Gamepad) => None,
+    };
+
+    let origin = players
+        .iter()
+        .find(|(_, controlled)| {
+            controlled.map_or(true, |controlled| controlled.0 == InputSource::Local)
+        })
+        .map(|(transform, _)| transform.translation.truncate());
+    let spread_gap = match (position, origin) {
+        (Some(position), Some(origin)) => position.distance(origin) * state.spread.tan(),
+        _ => 0.0,
+    };
+
+    if let Some(position) = position {
+        for mut transform in crosshairs.iter_mut() {
+            transform.translation = position.extend(CROSSHAIR_Z);
+        }
+    }
+
+    let color = if state.snap.is_some() {
+        style.snap_color
+    } else {
+        style.color
+    };
+    let offset = style.gap + spread_gap + style.length * 0.5;
+    for (arm, mut transform, mut sprite, mut visibility) in arms.iter_mut() {
+        visibility.is_visible = position.is_some();
+        transform.translation = Vec3::from((arm.direction * offset, 0.0));
+        if sprite.color != color {
+            sprite.color = color;
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use std::{fmt::Debug, hash::Hash};
+
+use bevy::{
+    core::Timer,
+    math::{Vec2, Vec3},
+    prelude::{
+        App, Commands, Component, Entity, EventWriter, ParallelSystemDescriptorCoercion, Plugin,
+        Query, Res, Transform, Without,
+    },
+};
+use iyes_loopless::prelude::IntoConditionalSystem;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    action::{Action, ActionState},
+    input::{Controlled, MoveInput, MoveSpeed, Velocity},
+    simulation::{SimulationTime, FIXED_UPDATE},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DashDirection {
+    Movement,
+    Aim,
+}
+
+impl Default for DashDirection {
+    fn default() -> Self {
+        Self::Movement
+    }
+}
+
+#[derive(Component, Debug)]
+pub struct DashAbility {
+    pub distance: f32,
+    pub duration: f32,
+    pub cooldown: f32,
+    pub invulnerability: f32,
+    pub direction: DashDirection,
+    cooldown_timer: Timer,
+}
+
+impl Default for DashAbility {
+    fn default() -> Self {
+        Self::new(96.0, 0.2, 0.6)
+    }
+}
+
+impl DashAbility {
+    pub fn new(distance: f32, duration: f32, cooldown: f32) -> Self {
+        let mut cooldown_timer = Timer::from_seconds(cooldown, false);
+        cooldown_timer.tick(cooldown_timer.duration());
+
+        Self {
+            distance,
+            duration,
+            cooldown,
+            invulnerability: duration,
+            direction: DashDirection::default(),
+            cooldown_timer,
+        }
+    }
+
+    pub fn with_direction(mut self, direction: DashDirection) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    pub fn ready(&self) -> bool {
+        self.cooldown_timer.finished()
+    }
+}
+
+#[derive(Component, Debug)]
+pub struct Dashing {
+    direction: Vec2,
+    speed: f32,
+    timer: Timer,
+}
+
+#[derive(Component, Debug)]
+pub struct Invulnerable(Timer);
+
+#[derive(Debug, Clone, Copy)]
+pub struct DashStarted {
+    pub entity: Entity,
+    pub direction: Vec2,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DashEnded {
+    pub entity: Entity,
+}
+
+fn start_dash(
+    mut commands: Commands,
+    actions: Res<ActionState>,
+    mut started: EventWriter<DashStarted>,
+    mut query: Query<
+        (
+            Entity,
+            &mut DashAbility,
+            &Transform,
+            &Controlled,
+            Option<&MoveInput>,
+        ),
+        Without<Dashing>,
+    >,
+) {
+    for (entity, mut dash, transform, controlled, input) in query.iter_mut() {
+        let pressed = actions
+            .source(controlled.0)
+            .map_or(false, |actions| actions.just_pressed(Action::Dash));
+        if !pressed || !dash.ready() || dash.duration <= 0.0 {
+            continue;
+        }
+
+        let facing = (transform.rotation * Vec3::X).truncate();
+        let direction = match dash.direction {
+            DashDirection::Movement => input
+                .map(|input| input.0.normalize_or_zero())
+                .filter(|direction| *direction != Vec2::ZERO)
+                .unwrap_or(facing),
+            DashDirection::Aim => facing,
+        };
+
+        commands.entity(entity).insert(Dashing {
+            direction,
+            speed: dash.distance / dash.duration,
+            timer: Timer::from_seconds(dash.duration, false),
+        });
+        if dash.invulnerability > 0.0 {
+            commands
+                .entity(entity)
+                .insert(Invulnerable(Timer::from_seconds(
+                    dash.invulnerability,
+                    false,
+                )));
+        }
+
+        let cooldown = dash.cooldown;
+        dash.cooldown_timer = Timer::from_seconds(cooldown, false);
+        started.send(DashStarted { entity, direction });
+    }
+}
+
+fn dash_movement(
+    mut commands: Commands,
+    time: Res<SimulationTime>,
+    mut ended: EventWriter<DashEnded>,
+    mut query: Query<(Entity, &mut Dashing, &mut Velocity, Option<&MoveSpeed>)>,
+) {
+    for (entity, mut dashing, mut velocity, speed) in query.iter_mut() {
+        dashing.timer.tick(time.delta());
+        velocity.0 = dashing.direction * dashing.speed;
+
+        if dashing.timer.finished() {
+            if let Some(speed) = speed {
+                velocity.0 = velocity.0.clamp_length_max(speed.0);
+            }
+            commands.entity(entity).remove::<Dashing>();
+            ended.send(DashEnded { entity });
+        }
+    }
+}
+
+fn dash_timers(
+    mut commands: Commands,
+    time: Res<SimulationTime>,
+    mut abilities: Query<&mut DashAbility>,
+    mut invulnerable: Query<(Entity, &mut Invulnerable)>,
+) {
+    for mut dash in abilities.iter_mut() {
+        dash.cooldown_timer.tick(time.delta());
+    }
+
+    for (entity, mut invulnerable) in invulnerable.iter_mut() {
+        if invulnerable.0.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<Invulnerable>();
+        }
+    }
+}
+
+pub trait DashState: Debug + Clone + Copy + PartialEq + Eq + Hash + Sync + Send {}
+
+#[derive(Default)]
+pub struct DashPlugin<T: DashState> {
+    state: T,
+}
+
+impl<T: 'static + DashState> DashPlugin<T> {
+    pub fn new(state: T) -> Self {
+        Self { state }
+    }
+}
+
+impl<T: 'static + DashState> Plugin for DashPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DashStarted>()
+            .add_event::<DashEnded>()
+            .add_system_to_stage(
+                FIXED_UPDATE,
+                dash_timers.run_in_state(self.state).before("start_dash"),
+            )
+            .add_system_to_stage(
+                FIXED_UPDATE,
+                start_dash
+                    .run_in_state(self.state)
+                    .label("start_dash")
+                    .after("update_action_state")
+                    .after("follow_move_target")
+                    .after("apply_aim"),
+            )
+            .add_system_to_stage(
+                FIXED_UPDATE,
+                dash_movement
+                    .run_in_state(self.state)
+                    .before("player_movement"),
+            );
+    }
+}
@@ -1,20 +1,40 @@
 use crate::{
-    animation::{AnimationPlugin, AnimationState, SpriteSheetAnimation},
+    action::{ActionPlugin, InputSource},
+    aim_assist::AimTarget,
+    animation::{AnimationPlugin, AnimationState, SpriteSheetAnimation, SpriteSheetAnimationState},
     animator::{animation_selection, AnimationKey, Animator},
-    camera::{CameraPlugin, CameraState},
-    input::{MovementPlugin, MovementState, Player, Velocity},
+    camera::{
+        CameraController, CameraLookahead, CameraPlugin, CameraState, CameraTarget, PixelPerfect,
+    },
+    camera_path::{CameraPathFinished, PlayCameraPath},
+    collision::{Collider, TileMap},
+    combat::{CombatPlugin, CombatState, Died, Health},
+    dash::{DashAbility, DashEnded, DashPlugin, DashStarted, DashState, Dashing},
+    input::{
+        Controlled, MoveInput, MoveSpeed, MovementModel, MovementPlugin, MovementState, Player,
+        Velocity,
+    },
     loading::{LoadingPlugin, LoadingState1},
-    mouse::{MousePlugin, MouseState},
+    melee::{MeleePlugin, MeleeState, MeleeSwing, MeleeWeapon},
+    mouse::{Aim, MousePlugin, MouseState},
+    navigation::{ClickToMove, NavigationPlugin, NavigationState},
+    replay::{ReplayPlugin, ReplayState},
+    settings::Settings,
+    simulation::{Interpolated, SimulationPlugin},
+    sprint::{Sprint, SprintPlugin, SprintState, Stamina},
+    validation::ValidationPlugin,
+    weapon::{Weapon, WeaponPlugin, WeaponState},
 };
 use bevy::{
     core::Name,
-    hierarchy::BuildChildren,
+    hierarchy::{BuildChildren, Children},
     math::Quat,
     prelude::{
-        info, AssetServer, Assets, Commands, Component, OrthographicCameraBundle, Plugin, Query,
-        Res, ResMut, SpriteSheetBundle, TextureAtlas, Transform, Vec2, Vec3,
+        debug, info, AssetServer, Assets, Changed, Color, Commands, Component, EventReader,
+        EventWriter, OrthographicCameraBundle, Plugin, Query, Res, ResMut, SpriteSheetBundle,
+        TextureAtlas, Transform, Vec2, Vec3, With,
     },
-    sprite::SpriteBundle,
+    sprite::{Sprite, SpriteBundle},
     transform::TransformBundle,
     utils::HashMap,
 };
@@ -24,11 +44,37 @@ use iyes_progress::ProgressPlugin;
 use std::f32::consts::TAU;
 
 const ANIMATION_FPS: u8 = 12;
+const SPRINT_ANIMATION_FPS: u8 = 18;
+const DASH_ANIMATION_FPS: u8 = 48;
+const MELEE_ANIMATION_FPS: u8 = 20;
+
+const ARENA_TILE_SIZE: f32 = 32.0;
+const ARENA: [&str; 12] = [
+    "####################",
+    "#..................#",
+    "#..................#",
+    "#....##......##....#",
+    "#....##......##....#",
+    "#..................#",
+    "#..................#",
+    "#....##......##....#",
+    "#....##......##....#",
+    "#..................#",
+    "#..................#",
+    "####################",
+];
+
+fn target_color(shade: f32) -> Color {
+    Color::rgb(0.8 * shade, 0.3 * shade, 0.3 * shade)
+}
 
 #[derive(Hash, PartialEq, Eq, Debug)]
 enum Animations {
     Idle,
     Walk,
+    Sprint,
+    Dash,
+    Attack(usize),
 }
 
 impl fmt::Display for Animations {
@@ -48,12 +94,22 @@ impl Default for Animations {
 #[derive(Component, Clone, Default, Debug)]
 struct AnimationData {
     moving: bool,
+    sprinting: bool,
+    dashing: bool,
+    attack: Option<usize>,
+    swings: u32,
 }
 
 fn animation_selector(data: AnimationData) -> Animations {
-    match data.moving {
-        true => Animations::Walk,
-        false => Animations::Idle,
+    if let (false, Some(step)) = (data.dashing, data.attack) {
+        return Animations::Attack(step);
+    }
+
+    match (data.dashing, data.moving, data.sprinting) {
+        (true, _, _) => Animations::Dash,
+        (false, true, true) => Animations::Sprint,
+        (false, true, false) => Animations::Walk,
+        (false, false, _) => Animations::Idle,
     }
 }
 
@@ -62,6 +118,8 @@ fn setup(
     asset_server: Res<AssetServer>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
     mut animations: ResMut<Assets<SpriteSheetAnimation>>,
+    mut camera_paths: EventWriter<PlayCameraPath>,
+    settings: Res<Settings>,
 ) {
     let texture_handle = asset_server.load("character/character-sheet.png");
     let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(64.0, 64.0), 11, 1);
@@ -73,18 +131,44 @@ fn setup(
         (1..11).collect(),
         ANIMATION_FPS,
     ));
+    let anim_sprint_handle = animations.add(SpriteSheetAnimation::from_frames(
+        (1..11).collect(),
+        SPRINT_ANIMATION_FPS,
+    ));
+    let anim_dash_handle = animations.add(SpriteSheetAnimation::once(
+        (1..11).collect(),
+        DASH_ANIMATION_FPS,
+    ));
+
+    let combo = [(1..6, 1..3), (6..11, 1..3), (1..11, 3..6)]
+        .into_iter()
+        .map(|(frames, active_frames)| {
+            let animation = animations.add(SpriteSheetAnimation::once(
+                frames.collect(),
+                MELEE_ANIMATION_FPS,
+            ));
+            MeleeSwing::new(animation, active_frames, 15.0)
+        })
+        .collect::<Vec<_>>();
 
     let player = Name::new("Player");
-    let animator = Animator::new(
-        HashMap::from_iter([
-            (Animations::Idle, anim_idle_handle),
-            (Animations::Walk, anim_walk_handle),
-        ]),
-        animation_selector,
-        player.clone(),
-    );
+    let mut anim_handles = HashMap::from_iter([
+        (Animations::Idle, anim_idle_handle),
+        (Animations::Walk, anim_walk_handle),
+        (Animations::Sprint, anim_sprint_handle),
+        (Animations::Dash, anim_dash_handle),
+    ]);
+    for (step, swing) in combo.iter().enumerate() {
+        anim_handles.insert(Animations::Attack(step), swing.animation.clone());
+    }
+    let animator = Animator::new(anim_handles, animation_selector, player.clone());
 
-    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
+    commands
+        .spawn_bundle(OrthographicCameraBundle::new_2d())
+        .insert(CameraController::default());
+    camera_paths.send(PlayCameraPath {
+        path: asset_server.load("camera/intro.camera.ron"),
+    });
 
     commands.spawn_bundle(SpriteBundle {
         texture: asset_server.load("background/cracked-dirt.png"),
@@ -92,11 +176,49 @@ fn setup(
         ..Default::default()
     });
 
+    let arena = TileMap::from_rows(&ARENA, Vec2::splat(ARENA_TILE_SIZE));
+    let arena_size = Vec2::new(arena.width as f32, arena.height as f32) * ARENA_TILE_SIZE;
+    let arena_origin = -arena_size * 0.5;
+    for (position, _) in arena.colliders_in(arena_origin, arena_origin, arena_origin + arena_size) {
+        commands.spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgb(0.35, 0.32, 0.3),
+                custom_size: Some(Vec2::splat(ARENA_TILE_SIZE)),
+                ..Default::default()
+            },
+            transform: Transform::from_translation(position.extend(0.5)),
+            ..Default::default()
+        });
+    }
+    commands
+        .spawn_bundle(TransformBundle::from_transform(
+            Transform::from_translation(arena_origin.extend(0.0)),
+        ))
+        .insert(Name::new("Arena"))
+        .insert(arena);
+
+    let gameplay = &settings.gameplay;
     commands
         .spawn_bundle(TransformBundle::from_transform(
             Transform::from_translation(Vec3::splat(0.)),
         ))
         .insert(Player {})
+        .insert(Controlled(InputSource::Local))
+        .insert(MoveInput::default())
+        .insert(ClickToMove::default())
+        .insert(MoveSpeed::default())
+        .insert(MovementModel::default())
+        .insert(Velocity::default())
+        .insert(Interpolated::default())
+        .insert(Collider::circle(12.0))
+        .insert(DashAbility::default().with_direction(gameplay.dash_direction))
+        .insert(Sprint::default())
+        .insert(Stamina::default())
+        .insert(Aim::new(gameplay.aim))
+        .insert(Weapon::default())
+        .insert(MeleeWeapon::new(combo))
+        .insert(CameraTarget::default())
+        .insert(CameraLookahead::new(gameplay.camera_lookahead))
         .insert(animator)
         .insert(AnimationData::default())
         .with_children(|parent| {
@@ -108,15 +230,89 @@ fn setup(
                 })
                 .insert(player.clone());
         });
+
+    for position in [
+        Vec2::new(192.0, 0.0),
+        Vec2::new(200.0, -60.0),
+        Vec2::new(-180.0, 90.0),
+    ] {
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: target_color(1.0),
+                    custom_size: Some(Vec2::new(24.0, 24.0)),
+                    ..Default::default()
+                },
+                transform: Transform::from_translation(position.extend(1.0)),
+                ..Default::default()
+            })
+            .insert(Name::new("Target"))
+            .insert(Collider::aabb(24.0, 24.0))
+            .insert(Health::new(50.0))
+            .insert(AimTarget::default());
+    }
+}
+
+fn tint_damaged_targets(
+    mut query: Query<(&Health, &mut Sprite), (With<AimTarget>, Changed<Health>)>,
+) {
+    for (health, mut sprite) in query.iter_mut() {
+        let fraction = (health.current() / health.max).clamp(0.0, 1.0);
+        sprite.color = target_color(0.4 + 0.6 * fraction);
+    }
 }
 
-fn update_animation_data(mut query: Query<(&Velocity, &mut AnimationData)>) {
-    for (velocity, mut anim_data) in query.iter_mut() {
+fn log_gameplay_events(
+    mut died: EventReader<Died>,
+    mut dash_started: EventReader<DashStarted>,
+    mut dash_ended: EventReader<DashEnded>,
+    mut camera_paths: EventReader<CameraPathFinished>,
+) {
+    for event in died.iter() {
+        info!("{:?} died, killed by {:?}", event.entity, event.killer);
+    }
+    for event in dash_started.iter() {
+        debug!("{:?} dashed toward {}", event.entity, event.direction);
+    }
+    for event in dash_ended.iter() {
+        debug!("{:?} finished dashing", event.entity);
+    }
+    for event in camera_paths.iter() {
+        info!("camera {:?} finished {:?}", event.camera, event.path);
+    }
+}
+
+fn update_animation_data(
+    mut query: Query<(
+        &Velocity,
+        Option<&Sprint>,
+        Option<&Dashing>,
+        Option<&MeleeWeapon>,
+        Option<&Children>,
+        &mut AnimationData,
+    )>,
+    mut sprites: Query<&mut SpriteSheetAnimationState>,
+) {
+    for (velocity, sprint, dashing, melee, children, mut anim_data) in query.iter_mut() {
         if velocity.length() > 0.0 {
             anim_data.moving = true;
         } else {
             anim_data.moving = false;
         }
+        anim_data.sprinting = sprint.map_or(false, |sprint| sprint.sprinting());
+        anim_data.dashing = dashing.is_some();
+        anim_data.attack = melee.and_then(|melee| melee.swing_step());
+
+        // A new swing may reuse the animation already playing, so start it over explicitly.
+        let swings = melee.map_or(0, |melee| melee.swings());
+        if swings != anim_data.swings {
+            anim_data.swings = swings;
+            for child in children.into_iter().flat_map(|children| children.iter()) {
+                if let Ok(mut state) = sprites.get_mut(*child) {
+                    state.restart();
+                }
+            }
+        }
     }
 }
 
@@ -130,6 +326,13 @@ impl AnimationState for GameState {}
 impl MovementState for GameState {}
 impl CameraState for GameState {}
 impl MouseState for GameState {}
+impl DashState for GameState {}
+impl SprintState for GameState {}
+impl ReplayState for GameState {}
+impl NavigationState for GameState {}
+impl CombatState for GameState {}
+impl WeaponState for GameState {}
+impl MeleeState for GameState {}
 impl LoadingState1 for GameState {}
 
 #[derive(Default)]
@@ -138,15 +341,30 @@ pub struct GooMainPlugin;
 impl Plugin for GooMainPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
         app.add_loopless_state(GameState::Loading)
+            .add_plugin(SimulationPlugin)
+            .add_plugin(ActionPlugin)
+            .add_plugin(ReplayPlugin::new(GameState::InGame))
             .add_plugin(LoadingPlugin::new(GameState::Loading, GameState::InGame))
             .add_plugin(AnimationPlugin::new(GameState::InGame))
             .add_plugin(MovementPlugin::new(GameState::InGame))
-            .add_plugin(CameraPlugin::new(GameState::InGame))
+            .add_plugin(DashPlugin::new(GameState::InGame))
+            .add_plugin(SprintPlugin::new(GameState::InGame))
+            .add_plugin(NavigationPlugin::new(GameState::InGame))
+            .add_plugin(
+                CameraPlugin::new(GameState::InGame)
+                    .with_pixel_perfect(PixelPerfect::new(640.0, 360.0).with_sprite_snapping()),
+            )
             .add_plugin(MousePlugin::new(GameState::InGame))
+            .add_plugin(CombatPlugin::new(GameState::InGame))
+            .add_plugin(WeaponPlugin::new(GameState::InGame))
+            .add_plugin(MeleePlugin::new(GameState::InGame))
+            .add_plugin(ValidationPlugin)
             .add_enter_system(GameState::InGame, setup)
             .add_system(
                 animation_selection::<Animations, AnimationData>.run_in_state(GameState::InGame),
             )
-            .add_system(update_animation_data.run_in_state(GameState::InGame));
+            .add_system(update_animation_data.run_in_state(GameState::InGame))
+            .add_system(tint_damaged_targets.run_in_state(GameState::InGame))
+            .add_system(log_gameplay_events.run_in_state(GameState::InGame));
     }
 }
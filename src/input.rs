@@ -1,23 +1,125 @@
-use std::{fmt::Debug, hash::Hash};
+use std::{cmp::Ordering, fmt::Debug, hash::Hash};
 
 use bevy::{
-    core::Time,
-    input::Input,
     math::{Vec2, Vec3},
     prelude::{
-        App, Commands, Component, CoreStage, Entity, KeyCode, Plugin, Query, Res, SystemSet,
-        Transform, With,
+        App, Component, Entity, ParallelSystemDescriptorCoercion, Plugin, Query, ReflectComponent,
+        Res, SystemSet, Transform, Without,
     },
+    reflect::Reflect,
 };
 use iyes_loopless::prelude::IntoConditionalSystem;
+use serde::{Deserialize, Serialize};
 
-const SPEED: f32 = 100.0;
+use crate::{
+    action::{Action, ActionSet, ActionState, InputSource},
+    collision::{move_and_slide, Collider, TileMap},
+    dash::Dashing,
+    simulation::{SimulationTime, FIXED_UPDATE},
+    sprint::Sprint,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SocdResolution {
+    LastPressed,
+    FirstPressed,
+    Neutral,
+}
+
+impl Default for SocdResolution {
+    fn default() -> Self {
+        Self::LastPressed
+    }
+}
+
+impl SocdResolution {
+    fn resolve(&self, actions: &ActionSet, negative: Action, positive: Action) -> f32 {
+        match (actions.pressed_at(negative), actions.pressed_at(positive)) {
+            (None, None) => 0.0,
+            (Some(_), None) => -1.0,
+            (None, Some(_)) => 1.0,
+            (Some(negative), Some(positive)) => {
+                let order = match self {
+                    SocdResolution::LastPressed => positive.cmp(&negative),
+                    SocdResolution::FirstPressed => negative.cmp(&positive),
+                    SocdResolution::Neutral => Ordering::Equal,
+                };
+                match order {
+                    Ordering::Greater => 1.0,
+                    Ordering::Less => -1.0,
+                    Ordering::Equal => 0.0,
+                }
+            }
+        }
+    }
+}
 
 #[derive(Component)]
 pub struct Player {}
 
-#[derive(Component)]
-pub struct Velocity(Vec2);
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Controlled(pub InputSource);
+
+impl Default for Controlled {
+    fn default() -> Self {
+        Self(InputSource::Local)
+    }
+}
+
+#[derive(Component, Debug, Clone, Copy, Reflect)]
+#[reflect(Component)]
+pub struct MoveSpeed(pub f32);
+
+impl Default for MoveSpeed {
+    fn default() -> Self {
+        Self(100.0)
+    }
+}
+
+#[derive(Component, Debug, Clone, Copy, Reflect)]
+#[reflect(Component)]
+pub struct MovementModel {
+    pub acceleration: f32,
+    pub deceleration: f32,
+    pub turn_responsiveness: f32,
+}
+
+impl Default for MovementModel {
+    fn default() -> Self {
+        Self {
+            acceleration: 800.0,
+            deceleration: 600.0,
+            turn_responsiveness: 1.0,
+        }
+    }
+}
+
+impl MovementModel {
+    fn step(&self, velocity: Vec2, desired: Vec2, delta: f32) -> Vec2 {
+        let rate = if desired == Vec2::ZERO {
+            self.deceleration
+        } else {
+            let alignment = velocity
+                .normalize_or_zero()
+                .dot(desired.normalize_or_zero());
+            self.acceleration * (1.0 + self.turn_responsiveness * (1.0 - alignment) * 0.5)
+        };
+
+        let difference = desired - velocity;
+        let max_step = rate * delta;
+        if difference.length() <= max_step {
+            desired
+        } else {
+            velocity + difference.normalize() * max_step
+        }
+    }
+}
+
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct MoveInput(pub Vec2);
+
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct Velocity(pub Vec2);
 
 impl Velocity {
     pub fn length(&self) -> f32 {
@@ -26,38 +128,108 @@ impl Velocity {
 }
 
 fn player_controller(
-    mut commands: Commands,
-    keyboard_input: Res<Input<KeyCode>>,
-    query: Query<Entity, With<Player>>,
+    actions: Res<ActionState>,
+    socd: Res<SocdResolution>,
+    mut query: Query<(&Controlled, &mut MoveInput)>,
 ) {
-    let mut vec2 = Vec2::default();
-    if keyboard_input.pressed(KeyCode::W) {
-        vec2.y = 1.0;
-    } else if keyboard_input.pressed(KeyCode::S) {
-        vec2.y = -1.0;
-    } else {
-        vec2.y = 0.0;
-    }
+    for (controlled, mut input) in query.iter_mut() {
+        let actions = match actions.source(controlled.0) {
+            Some(actions) => actions,
+            None => {
+                input.0 = Vec2::ZERO;
+                continue;
+            }
+        };
 
-    if keyboard_input.pressed(KeyCode::D) {
-        vec2.x = 1.0;
-    } else if keyboard_input.pressed(KeyCode::A) {
-        vec2.x = -1.0;
-    } else {
-        vec2.x = 0.0;
-    }
+        let mut vec2 = Vec2::new(
+            socd.resolve(actions, Action::MoveLeft, Action::MoveRight),
+            socd.resolve(actions, Action::MoveDown, Action::MoveUp),
+        );
 
-    let velocity = Velocity(vec2);
+        if vec2 == Vec2::ZERO {
+            vec2 = actions.move_stick();
+        }
+        input.0 = vec2.clamp_length_max(1.0);
+    }
+}
 
-    let entity = query.single();
-    commands.entity(entity).insert(velocity);
+fn apply_move_input(
+    time: Res<SimulationTime>,
+    mut query: Query<
+        (
+            &MoveInput,
+            &MoveSpeed,
+            Option<&MovementModel>,
+            Option<&Sprint>,
+            &mut Velocity,
+        ),
+        Without<Dashing>,
+    >,
+) {
+    let delta = time.delta_seconds();
+    for (input, speed, model, sprint, mut velocity) in query.iter_mut() {
+        let multiplier = sprint.map_or(1.0, |sprint| sprint.speed_multiplier());
+        let desired = input.0 * speed.0 * multiplier;
+        velocity.0 = match model {
+            Some(model) => model.step(velocity.0, desired, delta),
+            None => desired,
+        };
+    }
 }
 
-fn player_movement(time: Res<Time>, mut query: Query<(&mut Transform, &Velocity)>) {
+fn player_movement(
+    time: Res<SimulationTime>,
+    mut query: Query<(Entity, &mut Transform, &mut Velocity, Option<&Collider>)>,
+    colliders: Query<(&Transform, &Collider), Without<Velocity>>,
+    tilemaps: Query<(&Transform, &TileMap), Without<Velocity>>,
+) {
     let delta = time.delta_seconds();
-    for (mut transform, velocity) in query.iter_mut() {
-        let final_velocity = velocity.0 * SPEED * delta;
-        transform.translation += Vec3::from((final_velocity, 0.0));
+    let shapes = colliders
+        .iter()
+        .map(|(transform, collider)| (transform.translation.truncate(), *collider))
+        .collect::<Vec<_>>();
+    let mut bodies = query
+        .iter()
+        .filter_map(|(entity, transform, _, collider)| {
+            collider.map(|collider| (entity, transform.translation.truncate(), *collider))
+        })
+        .collect::<Vec<_>>();
+
+    for (entity, mut transform, mut velocity, collider) in query.iter_mut() {
+        let collider = match collider {
+            Some(collider) => collider,
+            None => {
+                let final_velocity = velocity.0 * delta;
+                transform.translation += Vec3::from((final_velocity, 0.0));
+                continue;
+            }
+        };
+
+        let position = transform.translation.truncate();
+        let motion = velocity.0 * delta;
+        let reach = collider.half_extents();
+        let min = position.min(position + motion) - reach;
+        let max = position.max(position + motion) + reach;
+
+        let mut obstacles = shapes.clone();
+        for (map_transform, tilemap) in tilemaps.iter() {
+            let origin = map_transform.translation.truncate();
+            obstacles.extend(tilemap.colliders_in(origin, min, max));
+        }
+        obstacles.extend(
+            bodies
+                .iter()
+                .filter(|(other, _, _)| *other != entity)
+                .map(|(_, position, collider)| (*position, *collider)),
+        );
+
+        let (position, slid) = move_and_slide(collider, position, velocity.0, delta, &obstacles);
+        if let Some(body) = bodies.iter_mut().find(|(other, _, _)| *other == entity) {
+            body.1 = position;
+        }
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+        velocity.0 = slid;
     }
 }
 
@@ -81,7 +253,123 @@ impl<T: 'static + MovementState> MovementPlugin<T> {
 
 impl<T: 'static + MovementState> Plugin for MovementPlugin<T> {
     fn build(&self, app: &mut App) {
-        app.add_system(player_controller.run_in_state(self.state))
-            .add_system(player_movement.run_in_state(self.state));
+        app.init_resource::<SocdResolution>()
+            .register_type::<MoveSpeed>()
+            .register_type::<MovementModel>()
+            .add_system_to_stage(
+                FIXED_UPDATE,
+                player_controller
+                    .run_in_state(self.state)
+                    .label("player_controller")
+                    .after("update_action_state")
+                    .before("apply_move_input"),
+            )
+            .add_system_to_stage(
+                FIXED_UPDATE,
+                apply_move_input
+                    .run_in_state(self.state)
+                    .label("apply_move_input")
+                    .before("player_movement"),
+            )
+            .add_system_to_stage(
+                FIXED_UPDATE,
+                player_movement
+                    .run_in_state(self.state)
+                    .label("player_movement"),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: f32 = 1.0 / 60.0;
+
+    fn hold(actions: &mut ActionSet, tick: u64, pressed: &[Action]) {
+        let pressed = pressed.iter().copied().collect();
+        actions.update(tick, pressed, Vec2::ZERO, Vec2::ZERO);
+    }
+
+    fn horizontal(actions: &ActionSet) -> [f32; 3] {
+        [
+            SocdResolution::LastPressed,
+            SocdResolution::FirstPressed,
+            SocdResolution::Neutral,
+        ]
+        .map(|socd| socd.resolve(actions, Action::MoveLeft, Action::MoveRight))
+    }
+
+    #[test]
+    fn simultaneous_presses_cancel_out() {
+        let mut actions = ActionSet::default();
+        hold(&mut actions, 1, &[Action::MoveLeft, Action::MoveRight]);
+
+        assert_eq!(horizontal(&actions), [0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn overlapping_presses_follow_the_mode() {
+        let mut actions = ActionSet::default();
+        hold(&mut actions, 1, &[Action::MoveLeft]);
+        assert_eq!(horizontal(&actions), [-1.0, -1.0, -1.0]);
+
+        hold(&mut actions, 2, &[Action::MoveLeft, Action::MoveRight]);
+        assert_eq!(horizontal(&actions), [1.0, -1.0, 0.0]);
+
+        // Letting go of the first key leaves the second one in charge in every mode.
+        hold(&mut actions, 3, &[Action::MoveRight]);
+        assert_eq!(horizontal(&actions), [1.0, 1.0, 1.0]);
+
+        // Pressing the first key again makes it the newest press.
+        hold(&mut actions, 4, &[Action::MoveLeft, Action::MoveRight]);
+        assert_eq!(horizontal(&actions), [-1.0, 1.0, 0.0]);
+    }
+
+    /// Steps toward `desired` until it is reached, returning every velocity along the way.
+    fn run(model: &MovementModel, mut velocity: Vec2, desired: Vec2) -> Vec<Vec2> {
+        let mut velocities = Vec::new();
+        for _ in 0..120 {
+            velocity = model.step(velocity, desired, STEP);
+            velocities.push(velocity);
+            if velocity == desired {
+                break;
+            }
+        }
+        velocities
+    }
+
+    #[test]
+    fn long_steps_land_on_the_desired_velocity() {
+        let model = MovementModel::default();
+        let desired = Vec2::new(100.0, 0.0);
+
+        assert_eq!(model.step(Vec2::ZERO, desired, 1.0), desired);
+        assert_eq!(model.step(desired, Vec2::ZERO, 1.0), Vec2::ZERO);
+        assert_eq!(model.step(-desired, desired, 1.0), desired);
+    }
+
+    #[test]
+    fn accelerating_never_overshoots() {
+        let model = MovementModel::default();
+        let desired = Vec2::new(100.0, 0.0);
+
+        let velocities = run(&model, Vec2::ZERO, desired);
+        assert_eq!(velocities.last(), Some(&desired));
+        assert!(velocities.iter().all(|velocity| velocity.x <= desired.x));
+    }
+
+    #[test]
+    fn stopping_and_turning_around_never_overshoot() {
+        let model = MovementModel::default();
+        let start = Vec2::new(100.0, 0.0);
+
+        let velocities = run(&model, start, Vec2::ZERO);
+        assert_eq!(velocities.last(), Some(&Vec2::ZERO));
+        assert!(velocities.iter().all(|velocity| velocity.x >= 0.0));
+
+        let velocities = run(&model, start, -start);
+        assert_eq!(velocities.last(), Some(&-start));
+        assert!(velocities.iter().all(|velocity| velocity.x >= -start.x));
     }
 }
@@ -1,16 +1,33 @@
+mod action;
+mod aim_assist;
 mod animation;
 mod animator;
 mod camera;
+mod camera_path;
+mod collision;
+mod combat;
+mod crosshair;
+mod dash;
 mod game;
 mod input;
 mod loading;
+mod melee;
 mod mouse;
+mod navigation;
+mod replay;
+mod settings;
+mod simulation;
+mod sprint;
+mod validation;
+mod weapon;
 
 use bevy::{prelude::App, DefaultPlugins};
 use game::GooMainPlugin;
+use settings::SettingsPlugin;
 
 fn main() {
     App::new()
+        .add_plugin(SettingsPlugin)
         .add_plugins(DefaultPlugins)
         .add_plugin(GooMainPlugin)
         .add_system(bevy::input::system::exit_on_esc_system)
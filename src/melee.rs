@@ -0,0 +1,330 @@
+use std::{
+    f32::consts::{PI, TAU},
+    fmt::Debug,
+    hash::Hash,
+    ops::Range,
+};
+
+use bevy::{
+    math::{Vec2, Vec3},
+    prelude::{
+        App, Assets, Component, Entity, EventWriter, Handle, ParallelSystemDescriptorCoercion,
+        Plugin, Query, Res, Transform, With,
+    },
+};
+use iyes_loopless::prelude::IntoConditionalSystem;
+
+use crate::{
+    action::{Action, ActionState},
+    animation::SpriteSheetAnimation,
+    collision::Collider,
+    combat::{Damage, Health},
+    input::Controlled,
+    simulation::{SimulationTime, FIXED_UPDATE},
+};
+
+/// One attack in a combo. The hitbox is live while `animation` shows a frame in `active_frames`.
+#[derive(Debug, Clone)]
+pub struct MeleeSwing {
+    pub animation: Handle<SpriteSheetAnimation>,
+    /// Positions in the animation's frame list, not sprite sheet indices.
+    pub active_frames: Range<usize>,
+    pub damage: f32,
+    pub range: f32,
+    pub arc_degrees: f32,
+}
+
+impl MeleeSwing {
+    pub fn new(
+        animation: Handle<SpriteSheetAnimation>,
+        active_frames: Range<usize>,
+        damage: f32,
+    ) -> Self {
+        Self {
+            animation,
+            active_frames,
+            damage,
+            range: 32.0,
+            arc_degrees: 120.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ActiveSwing {
+    step: usize,
+    elapsed: f32,
+    hit: Vec<Entity>,
+}
+
+#[derive(Component, Debug)]
+pub struct MeleeWeapon {
+    pub combo: Vec<MeleeSwing>,
+    /// Seconds after a swing ends in which pressing again continues the combo.
+    pub combo_window: f32,
+    swing: Option<ActiveSwing>,
+    queued: bool,
+    last_step: usize,
+    window: f32,
+    swings: u32,
+}
+
+impl MeleeWeapon {
+    pub fn new(combo: Vec<MeleeSwing>) -> Self {
+        Self {
+            combo,
+            combo_window: 0.35,
+            swing: None,
+            queued: false,
+            last_step: 0,
+            window: 0.0,
+            swings: 0,
+        }
+    }
+
+    /// Combo step of the swing in progress.
+    pub fn swing_step(&self) -> Option<usize> {
+        self.swing.as_ref().map(|swing| swing.step)
+    }
+
+    /// Counts every swing started, so a step repeated back to back can still be told apart.
+    pub fn swings(&self) -> u32 {
+        self.swings
+    }
+
+    fn next_step(&self) -> usize {
+        (self.last_step + 1) % self.combo.len()
+    }
+
+    fn press(&mut self) {
+        if self.combo.is_empty() {
+            return;
+        }
+        if self.swing.is_some() {
+            self.queued = true;
+            return;
+        }
+
+        let step = if self.window > 0.0 {
+            self.next_step()
+        } else {
+            0
+        };
+        self.start(step);
+    }
+
+    fn start(&mut self, step: usize) {
+        self.swing = Some(ActiveSwing {
+            step,
+            elapsed: 0.0,
+            hit: Vec::new(),
+        });
+        self.queued = false;
+        self.last_step = step;
+        self.window = 0.0;
+        self.swings = self.swings.wrapping_add(1);
+    }
+
+    fn finish(&mut self) {
+        self.swing = None;
+        if self.queued {
+            self.start(self.next_step());
+        } else {
+            self.window = self.combo_window;
+        }
+    }
+}
+
+/// Whether a circle of `radius` at `target` overlaps the arc in front of `origin`.
+fn in_arc(origin: Vec2, facing: Vec2, target: Vec2, radius: f32, swing: &MeleeSwing) -> bool {
+    let offset = target - origin;
+    let distance = offset.length();
+    if distance > swing.range + radius {
+        return false;
+    }
+    if distance <= radius {
+        return true;
+    }
+
+    let difference = offset.y.atan2(offset.x) - facing.y.atan2(facing.x);
+    let angle = ((difference + PI).rem_euclid(TAU) - PI).abs();
+    angle - (radius / distance).atan() <= swing.arc_degrees.to_radians() * 0.5
+}
+
+fn melee_attacks(
+    time: Res<SimulationTime>,
+    actions: Res<ActionState>,
+    animations: Res<Assets<SpriteSheetAnimation>>,
+    mut damage: EventWriter<Damage>,
+    mut attackers: Query<(Entity, &Controlled, &Transform, &mut MeleeWeapon)>,
+    targets: Query<(Entity, &Transform, Option<&Collider>), With<Health>>,
+) {
+    let delta = time.delta_seconds();
+    for (entity, controlled, transform, mut melee) in attackers.iter_mut() {
+        melee.window = (melee.window - delta).max(0.0);
+
+        let pressed = actions
+            .source(controlled.0)
+            .map_or(false, |actions| actions.just_pressed(Action::Melee));
+        if pressed {
+            melee.press();
+        }
+
+        let melee = &mut *melee;
+        let swing = match melee.swing.as_mut() {
+            Some(swing) => swing,
+            None => continue,
+        };
+        let definition = &melee.combo[swing.step];
+        let animation = match animations.get(&definition.animation) {
+            Some(animation) => animation,
+            None => continue,
+        };
+        if swing.elapsed >= animation.duration() {
+            melee.finish();
+            continue;
+        }
+
+        if definition
+            .active_frames
+            .contains(&animation.frame_at(swing.elapsed))
+        {
+            let origin = transform.translation.truncate();
+            let facing = transform.rotation.mul_vec3(Vec3::X).truncate();
+            for (target, target_transform, collider) in targets.iter() {
+                if target == entity || swing.hit.contains(&target) {
+                    continue;
+                }
+                let radius = collider.map_or(0.0, |collider| collider.half_extents().max_element());
+                let position = target_transform.translation.truncate();
+                if in_arc(origin, facing, position, radius, definition) {
+                    swing.hit.push(target);
+                    damage.send(Damage {
+                        target,
+                        amount: definition.damage,
+                        source: Some(entity),
+                    });
+                }
+            }
+        }
+        swing.elapsed += delta;
+    }
+}
+
+pub trait MeleeState: Debug + Clone + Copy + PartialEq + Eq + Hash + Sync + Send {}
+
+#[derive(Default)]
+pub struct MeleePlugin<T: MeleeState> {
+    state: T,
+}
+
+impl<T: 'static + MeleeState> MeleePlugin<T> {
+    pub fn new(state: T) -> Self {
+        Self { state }
+    }
+}
+
+impl<T: 'static + MeleeState> Plugin for MeleePlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.add_system_to_stage(
+            FIXED_UPDATE,
+            melee_attacks
+                .run_in_state(self.state)
+                .after("update_action_state")
+                .after("player_movement")
+                .after("apply_aim")
+                .before("apply_damage"),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weapon(steps: usize) -> MeleeWeapon {
+        MeleeWeapon::new(
+            (0..steps)
+                .map(|_| MeleeSwing::new(Handle::default(), 1..3, 10.0))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn presses_inside_the_combo_window_continue_the_combo() {
+        let mut melee = weapon(3);
+
+        melee.press();
+        assert_eq!(melee.swing_step(), Some(0));
+        melee.finish();
+        assert_eq!(melee.swing_step(), None);
+        assert_eq!(melee.window, melee.combo_window);
+
+        melee.press();
+        assert_eq!(melee.swing_step(), Some(1));
+        melee.finish();
+        melee.press();
+        assert_eq!(melee.swing_step(), Some(2));
+        melee.finish();
+        melee.press();
+        assert_eq!(melee.swing_step(), Some(0));
+    }
+
+    #[test]
+    fn combo_starts_over_once_the_window_closes() {
+        let mut melee = weapon(3);
+
+        melee.press();
+        melee.finish();
+        melee.window = 0.0;
+        melee.press();
+
+        assert_eq!(melee.swing_step(), Some(0));
+    }
+
+    #[test]
+    fn presses_during_a_swing_queue_the_next_step() {
+        let mut melee = weapon(3);
+
+        melee.press();
+        melee.press();
+        assert_eq!(melee.swing_step(), Some(0));
+        melee.finish();
+
+        assert_eq!(melee.swing_step(), Some(1));
+        assert_eq!(melee.window, 0.0);
+    }
+
+    #[test]
+    fn repeated_steps_count_as_new_swings() {
+        let mut melee = weapon(1);
+
+        melee.press();
+        melee.finish();
+        melee.press();
+
+        assert_eq!(melee.swing_step(), Some(0));
+        assert_eq!(melee.swings(), 2);
+    }
+
+    #[test]
+    fn arc_edges() {
+        let swing = MeleeSwing::new(Handle::default(), 0..1, 10.0);
+        let half_arc = (swing.arc_degrees * 0.5).to_radians();
+        let at = |angle: f32, distance: f32| Vec2::new(angle.cos(), angle.sin()) * distance;
+        let hits = |target: Vec2, radius: f32| in_arc(Vec2::ZERO, Vec2::X, target, radius, &swing);
+
+        assert!(hits(at(half_arc - 0.01, 20.0), 0.0));
+        assert!(hits(at(-half_arc + 0.01, 20.0), 0.0));
+        assert!(!hits(at(half_arc + 0.01, 20.0), 0.0));
+        assert!(!hits(at(-half_arc - 0.01, 20.0), 0.0));
+        // A target's radius lets it poke into the arc from outside.
+        assert!(hits(at(half_arc + 0.1, 20.0), 4.0));
+
+        assert!(hits(at(0.0, swing.range - 0.01), 0.0));
+        assert!(!hits(at(0.0, swing.range + 0.01), 0.0));
+        assert!(hits(at(0.0, swing.range + 3.0), 4.0));
+        // Targets overlapping the attacker are hit from any side.
+        assert!(hits(at(PI, 2.0), 4.0));
+    }
+}
@@ -1,65 +1,298 @@
-use std::f32::consts::{FRAC_PI_2, PI};
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
 use std::{fmt::Debug, hash::Hash};
 
 use bevy::{
     input::mouse::MouseMotion,
     math::{Quat, Vec2, Vec3},
     prelude::{
-        Camera, CoreStage, EventReader, GlobalTransform, Local, ParamSet, Plugin, Query, Res,
-        SystemSet, Transform, With,
+        Camera, Component, CoreStage, Entity, EventReader, GlobalTransform,
+        ParallelSystemDescriptorCoercion, Plugin, Query, Res, ResMut, SystemSet, Transform, With,
+        Without,
     },
     render::camera::RenderTarget,
-    window::{Window, Windows},
+    ui::entity::CameraUi,
+    utils::HashMap,
+    window::{Window, WindowId, Windows},
 };
-use iyes_loopless::prelude::IntoConditionalSystem;
+use iyes_loopless::prelude::{AppLooplessStateExt, IntoConditionalSystem};
+use serde::{Deserialize, Serialize};
 
-use crate::input::Player;
+use crate::{
+    action::{ActionState, InputSource},
+    aim_assist::{aim_assist, AimAssist},
+    crosshair::{
+        despawn_crosshair, hide_os_cursor, show_os_cursor, spawn_crosshair, update_crosshair,
+        CrosshairState, CrosshairStyle,
+    },
+    input::{Controlled, Player},
+    replay::Replay,
+    simulation::{SimulationTime, FIXED_UPDATE},
+};
 
-fn mouse_look(
+const MIN_AIM_DISTANCE: f32 = 4.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CameraCursor {
+    position: Vec2,
+    view_offset: Vec2,
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct CursorWorldPosition {
+    primary: Option<CameraCursor>,
+    camera: Option<Entity>,
+    window: Option<WindowId>,
+    cameras: HashMap<Entity, CameraCursor>,
+}
+
+impl CursorWorldPosition {
+    /// World position under the primary camera, the one on the primary window if the cursor
+    /// is there. This is what to use with a single camera.
+    pub fn get(&self) -> Option<Vec2> {
+        self.primary.map(|cursor| cursor.position)
+    }
+
+    /// Cursor offset from the center of the primary camera's view in world units. Unlike
+    /// [`Self::get`] this doesn't change when the camera moves.
+    pub fn view_offset(&self) -> Option<Vec2> {
+        self.primary.map(|cursor| cursor.view_offset)
+    }
+
+    /// Camera that [`Self::get`] was resolved through. `None` during replay playback.
+    pub fn camera(&self) -> Option<Entity> {
+        self.camera
+    }
+
+    pub fn window(&self) -> Option<WindowId> {
+        self.window
+    }
+
+    /// World position of the cursor as seen through `camera`, if it is over that camera's window.
+    pub fn for_camera(&self, camera: Entity) -> Option<Vec2> {
+        self.cameras.get(&camera).map(|cursor| cursor.position)
+    }
+
+    fn insert(&mut self, camera: Entity, window: WindowId, cursor: CameraCursor) {
+        self.cameras.insert(camera, cursor);
+
+        // Query order isn't stable, so pick the primary camera by window and then entity.
+        let rank = |window: WindowId, camera: Entity| (window != WindowId::primary(), camera);
+        let replace = match (self.camera, self.window) {
+            (Some(current), Some(current_window)) => {
+                rank(window, camera) < rank(current_window, current)
+            }
+            _ => true,
+        };
+        if replace {
+            self.primary = Some(cursor);
+            self.camera = Some(camera);
+            self.window = Some(window);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AimSource {
+    Mouse,
+    Gamepad,
+}
+
+impl Default for AimSource {
+    fn default() -> Self {
+        Self::Mouse
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum AimMode {
+    Instant,
+    /// Turns toward the target at a fixed angular speed.
+    MaxSpeed {
+        degrees_per_second: f32,
+    },
+    /// Closes the remaining angle exponentially; higher rates turn faster.
+    Smoothed {
+        rate: f32,
+    },
+}
+
+impl Default for AimMode {
+    fn default() -> Self {
+        Self::Smoothed { rate: 13.4 }
+    }
+}
+
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Aim {
+    pub mode: AimMode,
+    direction: Option<Vec2>,
+    slowdown: f32,
+}
+
+impl Default for Aim {
+    fn default() -> Self {
+        Self::new(AimMode::default())
+    }
+}
+
+impl Aim {
+    pub fn new(mode: AimMode) -> Self {
+        Self {
+            mode,
+            direction: None,
+            slowdown: 0.0,
+        }
+    }
+
+    pub fn direction(&self) -> Option<Vec2> {
+        self.direction
+    }
+
+    /// Sets the direction to face, or `None` to keep the current facing.
+    /// Zero or non-finite directions are ignored.
+    pub fn set_direction(&mut self, direction: Option<Vec2>) {
+        match direction {
+            Some(direction) if !direction.is_finite() || direction == Vec2::ZERO => {}
+            _ => self.direction = direction,
+        }
+    }
+
+    /// Fraction of turn speed to take away this frame, used by aim assist.
+    pub fn set_slowdown(&mut self, slowdown: f32) {
+        self.slowdown = slowdown.clamp(0.0, 1.0);
+    }
+
+    fn turn(&self, current: f32, target: f32, delta: f32) -> f32 {
+        let delta = delta * (1.0 - self.slowdown);
+        let difference = (target - current + PI).rem_euclid(TAU) - PI;
+        let step = match self.mode {
+            AimMode::Instant => difference,
+            AimMode::MaxSpeed { degrees_per_second } => {
+                let max_step = degrees_per_second.to_radians() * delta;
+                difference.clamp(-max_step, max_step)
+            }
+            AimMode::Smoothed { rate } => difference * (1.0 - (-rate * delta).exp()),
+        };
+        current + step
+    }
+}
+
+fn aim_source(
+    mut motion: EventReader<MouseMotion>,
+    actions: Res<ActionState>,
+    replay: Option<Res<Replay>>,
+    mut source: ResMut<AimSource>,
+) {
+    if let Some(frame) = replay.as_ref().and_then(|replay| replay.frame()) {
+        if *source != frame.aim_source {
+            *source = frame.aim_source;
+        }
+        return;
+    }
+
+    let mouse_moved = motion.iter().any(|event| event.delta != Vec2::ZERO);
+    if mouse_moved {
+        if *source != AimSource::Mouse {
+            *source = AimSource::Mouse;
+        }
+    } else if actions.local().aim_stick().is_some() && *source != AimSource::Gamepad {
+        *source = AimSource::Gamepad;
+    }
+}
+
+fn cursor_world_position(
     wnds: Res<Windows>,
-    mut params: ParamSet<(
-        Query<(&Camera, &GlobalTransform)>,
-        Query<&mut Transform, With<Player>>,
-    )>,
-    mut mouse_pos: Local<Option<Vec3>>,
+    cameras: Query<(Entity, &Camera, &GlobalTransform), Without<CameraUi>>,
+    replay: Option<Res<Replay>>,
+    mut cursor: ResMut<CursorWorldPosition>,
 ) {
-    for (camera, camera_transform) in params.p0().iter() {
-        let wnd = if let RenderTarget::Window(id) = camera.target {
-            wnds.get(id).unwrap()
-        } else {
-            wnds.get_primary().unwrap()
+    *cursor = CursorWorldPosition::default();
+
+    if let Some(frame) = replay.as_ref().and_then(|replay| replay.frame()) {
+        cursor.primary = frame
+            .cursor
+            .zip(frame.cursor_offset)
+            .map(|(position, view_offset)| CameraCursor {
+                position,
+                view_offset,
+            });
+        return;
+    }
+
+    for (entity, camera, camera_transform) in cameras.iter() {
+        let window_id = match camera.target {
+            RenderTarget::Window(id) => id,
+            _ => WindowId::primary(),
+        };
+        let wnd = match wnds.get(window_id) {
+            Some(wnd) => wnd,
+            None => continue,
         };
 
         if let Some(screen_pos) = wnd.cursor_position() {
-            //*mouse_pos = Some(screen_pos.extend(-1.0));
             let window_size = Vec2::new(wnd.width() as f32, wnd.height() as f32);
-
             let ndc = (screen_pos / window_size) * 2.0 - Vec2::ONE;
 
-            let ndc_to_world =
-                camera_transform.compute_matrix() * camera.projection_matrix.inverse();
-
-            let world_pos = ndc_to_world.project_point3(ndc.extend(-1.0));
+            let ndc_to_view = camera.projection_matrix.inverse();
+            let view_pos = ndc_to_view.project_point3(ndc.extend(-1.0));
 
-            //eprintln!("World coords: {}/{}", world_pos.x, world_pos.y);
-            *mouse_pos = Some(Vec3::new(world_pos.x, world_pos.y, 1.0));
-        } else {
-            *mouse_pos = None;
+            let camera_cursor = CameraCursor {
+                position: camera_transform.mul_vec3(view_pos).truncate(),
+                view_offset: view_pos.truncate(),
+            };
+            cursor.insert(entity, window_id, camera_cursor);
         }
     }
+}
 
-    if let Some(mouse_pos) = *mouse_pos {
-        for mut transform in params.p1().iter_mut() {
-            let mouse_pos_2d = mouse_pos.truncate();
-            let transform_2d = transform.translation.truncate();
-            let v = mouse_pos_2d - transform_2d;
-            let b = v.normalize();
-            let a = Vec2::new(1.0, 0.0);
-            let new_rotation = Quat::from_rotation_arc_2d(a, b).normalize();
+/// Direction to aim in for the active aim source. A stick direction is used as is, since
+/// the dead zone already filtered it; the cursor is ignored when it sits on the player.
+fn aim_direction(
+    source: AimSource,
+    aim_stick: Option<Vec2>,
+    cursor_offset: Option<Vec2>,
+) -> Option<Vec2> {
+    match source {
+        AimSource::Gamepad => aim_stick,
+        AimSource::Mouse => cursor_offset.filter(|offset| offset.length() >= MIN_AIM_DISTANCE),
+    }
+}
 
-            let old_rotation = transform.rotation;
-            transform.rotation = old_rotation.lerp(new_rotation, 0.2);
-        }
+fn mouse_look(
+    mut players: Query<(&Transform, &mut Aim, Option<&Controlled>), With<Player>>,
+    cursor: Res<CursorWorldPosition>,
+    actions: Res<ActionState>,
+    source: Res<AimSource>,
+) {
+    for (transform, mut aim, controlled) in players.iter_mut() {
+        let input = controlled.map_or(InputSource::Local, |controlled| controlled.0);
+        let source = match input {
+            InputSource::Gamepad(_) => AimSource::Gamepad,
+            InputSource::Local => *source,
+        };
+        let aim_stick = actions
+            .source(input)
+            .and_then(|actions| actions.aim_stick());
+        let cursor_offset = cursor
+            .get()
+            .map(|mouse_pos_2d| mouse_pos_2d - transform.translation.truncate());
+
+        aim.set_direction(aim_direction(source, aim_stick, cursor_offset));
+    }
+}
+
+fn apply_aim(time: Res<SimulationTime>, mut query: Query<(&mut Transform, &Aim)>) {
+    let delta = time.delta_seconds();
+    for (mut transform, aim) in query.iter_mut() {
+        let direction = match aim.direction() {
+            Some(direction) => direction,
+            None => continue,
+        };
+
+        let facing = (transform.rotation * Vec3::X).truncate();
+        let current = facing.y.atan2(facing.x);
+        let target = direction.y.atan2(direction.x);
+        transform.rotation = Quat::from_rotation_z(aim.turn(current, target, delta));
     }
 }
 
@@ -78,6 +311,122 @@ impl<T: 'static + MouseState> MousePlugin<T> {
 
 impl<T: 'static + MouseState> Plugin for MousePlugin<T> {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.add_system(mouse_look.run_in_state(self.state));
+        app.init_resource::<CursorWorldPosition>()
+            .init_resource::<AimSource>()
+            .init_resource::<CrosshairStyle>()
+            .init_resource::<CrosshairState>()
+            .init_resource::<AimAssist>()
+            .add_enter_system(self.state, spawn_crosshair)
+            .add_enter_system(self.state, hide_os_cursor)
+            .add_exit_system(self.state, despawn_crosshair)
+            .add_exit_system(self.state, show_os_cursor)
+            .add_system_to_stage(CoreStage::PreUpdate, cursor_world_position)
+            .add_system_to_stage(
+                FIXED_UPDATE,
+                aim_source
+                    .run_in_state(self.state)
+                    .label("aim_source")
+                    .after("update_action_state"),
+            )
+            .add_system_to_stage(
+                FIXED_UPDATE,
+                mouse_look
+                    .run_in_state(self.state)
+                    .label("mouse_look")
+                    .after("aim_source")
+                    .after("player_movement"),
+            )
+            .add_system_to_stage(
+                FIXED_UPDATE,
+                aim_assist
+                    .run_in_state(self.state)
+                    .label("aim_assist")
+                    .after("mouse_look")
+                    .before("apply_aim"),
+            )
+            .add_system_to_stage(
+                FIXED_UPDATE,
+                apply_aim
+                    .run_in_state(self.state)
+                    .label("apply_aim")
+                    .after("mouse_look"),
+            )
+            .add_system(
+                update_crosshair
+                    .run_in_state(self.state)
+                    .label("update_crosshair")
+                    .after("camera_movement"),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera_cursor(x: f32) -> CameraCursor {
+        CameraCursor {
+            position: Vec2::new(x, 0.0),
+            view_offset: Vec2::ZERO,
+        }
+    }
+
+    #[test]
+    fn cursor_is_kept_per_camera() {
+        let mut cursor = CursorWorldPosition::default();
+        let (first, second, other) = (
+            Entity::from_raw(1),
+            Entity::from_raw(2),
+            Entity::from_raw(0),
+        );
+        let other_window = WindowId::new();
+
+        cursor.insert(second, WindowId::primary(), camera_cursor(2.0));
+        cursor.insert(other, other_window, camera_cursor(0.0));
+        cursor.insert(first, WindowId::primary(), camera_cursor(1.0));
+
+        assert_eq!(cursor.for_camera(first), Some(Vec2::new(1.0, 0.0)));
+        assert_eq!(cursor.for_camera(second), Some(Vec2::new(2.0, 0.0)));
+        assert_eq!(cursor.for_camera(other), Some(Vec2::ZERO));
+        assert_eq!(cursor.for_camera(Entity::from_raw(3)), None);
+
+        // The primary window wins over entity order, then the lowest entity.
+        assert_eq!(cursor.camera(), Some(first));
+        assert_eq!(cursor.window(), Some(WindowId::primary()));
+        assert_eq!(cursor.get(), Some(Vec2::new(1.0, 0.0)));
+    }
+
+    #[test]
+    fn unit_stick_direction_reaches_aim() {
+        let mut aim = Aim::default();
+        let stick = Vec2::new(0.6, -0.8);
+
+        aim.set_direction(aim_direction(AimSource::Gamepad, Some(stick), None));
+
+        assert_eq!(aim.direction(), Some(stick));
+    }
+
+    #[test]
+    fn short_sticks_still_aim() {
+        let mut aim = Aim::default();
+        let stick = Vec2::new(0.3, 0.2);
+
+        aim.set_direction(aim_direction(AimSource::Gamepad, Some(stick), None));
+
+        assert_eq!(aim.direction(), Some(stick));
+    }
+
+    #[test]
+    fn cursor_on_the_player_keeps_facing() {
+        let mut aim = Aim::default();
+
+        // No direction leaves the current rotation alone in `apply_aim`.
+        let offset = Vec2::new(1.0, 2.0);
+        aim.set_direction(aim_direction(AimSource::Mouse, None, Some(offset)));
+        assert_eq!(aim.direction(), None);
+
+        let offset = Vec2::new(0.0, -20.0);
+        aim.set_direction(aim_direction(AimSource::Mouse, None, Some(offset)));
+        assert_eq!(aim.direction(), Some(offset));
     }
 }
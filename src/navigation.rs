@@ -0,0 +1,360 @@
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+};
+use std::{fmt::Debug, hash::Hash};
+
+use bevy::{
+    math::Vec2,
+    prelude::{App, Component, ParallelSystemDescriptorCoercion, Plugin, Query, Res, Transform},
+    utils::HashMap,
+};
+use iyes_loopless::prelude::IntoConditionalSystem;
+
+use crate::{
+    action::{Action, ActionSet, ActionState},
+    collision::TileMap,
+    input::{Controlled, MoveInput},
+    mouse::CursorWorldPosition,
+    simulation::FIXED_UPDATE,
+};
+
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+
+/// Moves an entity to a point picked with the `MoveTo` action instead of steering it directly.
+/// Direct movement input from a controlled entity's input source cancels the current target.
+#[derive(Component, Debug, Clone)]
+pub struct ClickToMove {
+    pub arrival_radius: f32,
+    pub slowing_radius: f32,
+    target: Option<Vec2>,
+    path: Vec<Vec2>,
+    needs_path: bool,
+}
+
+impl Default for ClickToMove {
+    fn default() -> Self {
+        Self {
+            arrival_radius: 4.0,
+            slowing_radius: 24.0,
+            target: None,
+            path: Vec::new(),
+            needs_path: false,
+        }
+    }
+}
+
+impl ClickToMove {
+    pub fn set_target(&mut self, target: Option<Vec2>) {
+        self.target = target;
+        self.path.clear();
+        self.needs_path = target.is_some();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Node {
+    cost: u32,
+    tile: (usize, usize),
+}
+
+impl Ord for Node {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.cost
+            .cmp(&other.cost)
+            .then_with(|| self.tile.cmp(&other.tile))
+    }
+}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn octile_distance(a: (usize, usize), b: (usize, usize)) -> u32 {
+    let dx = (a.0 as i64 - b.0 as i64).unsigned_abs() as u32;
+    let dy = (a.1 as i64 - b.1 as i64).unsigned_abs() as u32;
+    STRAIGHT_COST * dx.max(dy) + (DIAGONAL_COST - STRAIGHT_COST) * dx.min(dy)
+}
+
+/// A* over the free tiles of `map`. Diagonal steps never cut past a solid corner.
+/// Returns waypoints at tile centers, ending exactly at `to`.
+pub fn find_path(map: &TileMap, origin: Vec2, from: Vec2, to: Vec2) -> Option<Vec<Vec2>> {
+    let start = map.tile_at(origin, from)?;
+    let goal = map.tile_at(origin, to)?;
+    if map.is_solid(goal.0, goal.1) {
+        return None;
+    }
+
+    let mut open = BinaryHeap::new();
+    let mut came_from: HashMap<(usize, usize), (usize, usize)> = HashMap::default();
+    let mut costs: HashMap<(usize, usize), u32> = HashMap::default();
+    open.push(Reverse(Node {
+        cost: octile_distance(start, goal),
+        tile: start,
+    }));
+    costs.insert(start, 0);
+
+    while let Some(Reverse(Node { tile, .. })) = open.pop() {
+        if tile == goal {
+            let mut path = vec![to];
+            let mut current = tile;
+            while let Some(previous) = came_from.get(&current) {
+                if *previous != start {
+                    path.push(map.tile_center(origin, previous.0, previous.1));
+                }
+                current = *previous;
+            }
+            path.reverse();
+            return Some(path);
+        }
+
+        let cost = costs[&tile];
+        for dx in -1i64..=1 {
+            for dy in -1i64..=1 {
+                if dx == 0 && dy == 0 {
+                    continue;
+                }
+                let (x, y) = (tile.0 as i64 + dx, tile.1 as i64 + dy);
+                if x < 0 || y < 0 || x as usize >= map.width || y as usize >= map.height {
+                    continue;
+                }
+                let next = (x as usize, y as usize);
+                if map.is_solid(next.0, next.1) {
+                    continue;
+                }
+                let diagonal = dx != 0 && dy != 0;
+                if diagonal && (map.is_solid(next.0, tile.1) || map.is_solid(tile.0, next.1)) {
+                    continue;
+                }
+
+                let next_cost = cost
+                    + if diagonal {
+                        DIAGONAL_COST
+                    } else {
+                        STRAIGHT_COST
+                    };
+                if costs.get(&next).map_or(true, |known| next_cost < *known) {
+                    costs.insert(next, next_cost);
+                    came_from.insert(next, tile);
+                    open.push(Reverse(Node {
+                        cost: next_cost + octile_distance(next, goal),
+                        tile: next,
+                    }));
+                }
+            }
+        }
+    }
+
+    None
+}
+
+fn set_move_target(
+    actions: Res<ActionState>,
+    cursor: Res<CursorWorldPosition>,
+    mut query: Query<(&Controlled, &mut ClickToMove)>,
+) {
+    let target = match cursor.get() {
+        Some(target) => target,
+        None => return,
+    };
+
+    for (controlled, mut click) in query.iter_mut() {
+        let pressed = actions
+            .source(controlled.0)
+            .map_or(false, |actions| actions.just_pressed(Action::MoveTo));
+        if pressed {
+            click.set_target(Some(target));
+        }
+    }
+}
+
+fn steering(actions: &ActionSet) -> bool {
+    actions.move_stick() != Vec2::ZERO
+        || [
+            Action::MoveUp,
+            Action::MoveDown,
+            Action::MoveLeft,
+            Action::MoveRight,
+        ]
+        .into_iter()
+        .any(|action| actions.pressed(action))
+}
+
+fn follow_move_target(
+    actions: Res<ActionState>,
+    tilemaps: Query<(&Transform, &TileMap)>,
+    mut query: Query<(
+        &Transform,
+        &mut ClickToMove,
+        &mut MoveInput,
+        Option<&Controlled>,
+    )>,
+) {
+    for (transform, mut click, mut input, controlled) in query.iter_mut() {
+        let target = match click.target {
+            Some(target) => target,
+            None => continue,
+        };
+        // `player_controller` already wrote the direct input, so it's left as is.
+        let steered = controlled
+            .and_then(|controlled| actions.source(controlled.0))
+            .map_or(false, steering);
+        if steered {
+            click.set_target(None);
+            continue;
+        }
+
+        let position = transform.translation.truncate();
+        if click.needs_path {
+            let map = tilemaps.iter().find_map(|(map_transform, map)| {
+                let origin = map_transform.translation.truncate();
+                map.tile_at(origin, position).map(|_| (origin, map))
+            });
+            // Off every map there's nothing to path around, so head straight for the target.
+            let path = match map {
+                Some((origin, map)) => find_path(map, origin, position, target),
+                None => Some(vec![target]),
+            };
+            click.needs_path = false;
+
+            match path {
+                Some(path) => click.path = path,
+                None => {
+                    click.set_target(None);
+                    input.0 = Vec2::ZERO;
+                    continue;
+                }
+            }
+        }
+
+        while click.path.len() > 1 && position.distance(click.path[0]) <= click.arrival_radius {
+            click.path.remove(0);
+        }
+
+        let waypoint = match click.path.first() {
+            Some(waypoint) => *waypoint,
+            None => {
+                click.set_target(None);
+                input.0 = Vec2::ZERO;
+                continue;
+            }
+        };
+        let offset = waypoint - position;
+        let distance = offset.length();
+        if click.path.len() == 1 && distance <= click.arrival_radius {
+            click.set_target(None);
+            input.0 = Vec2::ZERO;
+            continue;
+        }
+
+        let speed = if click.path.len() == 1 && click.slowing_radius > 0.0 {
+            (distance / click.slowing_radius).min(1.0)
+        } else {
+            1.0
+        };
+        input.0 = offset / distance * speed;
+    }
+}
+
+pub trait NavigationState: Debug + Clone + Copy + PartialEq + Eq + Hash + Sync + Send {}
+
+#[derive(Default)]
+pub struct NavigationPlugin<T: NavigationState> {
+    state: T,
+}
+
+impl<T: 'static + NavigationState> NavigationPlugin<T> {
+    pub fn new(state: T) -> Self {
+        Self { state }
+    }
+}
+
+impl<T: 'static + NavigationState> Plugin for NavigationPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.add_system_to_stage(
+            FIXED_UPDATE,
+            set_move_target
+                .run_in_state(self.state)
+                .after("update_action_state")
+                .before("follow_move_target"),
+        )
+        .add_system_to_stage(
+            FIXED_UPDATE,
+            follow_move_target
+                .run_in_state(self.state)
+                .label("follow_move_target")
+                .after("player_controller")
+                .before("apply_move_input"),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TILE: f32 = 10.0;
+
+    fn center(x: usize, y: usize) -> Vec2 {
+        (Vec2::new(x as f32, y as f32) + 0.5) * TILE
+    }
+
+    fn map(rows: &[&str]) -> TileMap {
+        TileMap::from_rows(rows, Vec2::splat(TILE))
+    }
+
+    #[test]
+    fn straight_path_visits_each_tile() {
+        let map = map(&["....."]);
+        let to = center(4, 0) + Vec2::new(2.0, 1.0);
+
+        let path = find_path(&map, Vec2::ZERO, center(0, 0), to).unwrap();
+
+        assert_eq!(path, vec![center(1, 0), center(2, 0), center(3, 0), to]);
+    }
+
+    #[test]
+    fn detours_around_walls() {
+        let map = map(&[".....", "..#..", "..#.."]);
+        let to = center(4, 0);
+
+        let path = find_path(&map, Vec2::ZERO, center(0, 0), to).unwrap();
+
+        assert_eq!(path.last(), Some(&to));
+        assert!(path.contains(&center(2, 2)), "{:?}", path);
+        for waypoint in path.iter() {
+            let (x, y) = map.tile_at(Vec2::ZERO, *waypoint).unwrap();
+            assert!(!map.is_solid(x, y), "{:?}", path);
+        }
+    }
+
+    #[test]
+    fn unreachable_goals_have_no_path() {
+        let map = map(&["...#.", "...#.", "...#."]);
+
+        assert_eq!(
+            find_path(&map, Vec2::ZERO, center(0, 0), center(4, 1)),
+            None
+        );
+        assert_eq!(
+            find_path(&map, Vec2::ZERO, center(0, 0), center(3, 1)),
+            None
+        );
+        assert_eq!(
+            find_path(&map, Vec2::ZERO, center(0, 0), center(9, 0)),
+            None
+        );
+    }
+
+    #[test]
+    fn starting_inside_a_wall_walks_out() {
+        let map = map(&["#...."]);
+
+        let path = find_path(&map, Vec2::ZERO, center(0, 0), center(3, 0)).unwrap();
+
+        assert_eq!(path, vec![center(1, 0), center(2, 0), center(3, 0)]);
+    }
+}
@@ -0,0 +1,240 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+use std::{fmt::Debug, hash::Hash};
+
+use bevy::{
+    app::AppExit,
+    math::Vec2,
+    prelude::{
+        info, warn, App, CoreStage, EventReader, ParallelSystemDescriptorCoercion, Plugin, Res,
+        ResMut,
+    },
+};
+use iyes_loopless::prelude::IntoConditionalSystem;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    action::{Action, ActionState},
+    mouse::{AimSource, CursorWorldPosition},
+    simulation::{SimulationTime, FIXED_UPDATE},
+};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReplayFrame {
+    pub time: f64,
+    pub pressed: Vec<Action>,
+    pub move_stick: Vec2,
+    pub aim_stick: Vec2,
+    pub aim_source: AimSource,
+    pub cursor: Option<Vec2>,
+    #[serde(default)]
+    pub cursor_offset: Option<Vec2>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InputRecording {
+    pub step: f32,
+    pub frames: Vec<ReplayFrame>,
+}
+
+impl InputRecording {
+    pub fn new(step: f32) -> Self {
+        Self {
+            step,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        Ok(ron::from_str(&contents)?)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let contents = ron::ser::to_string_pretty(self, PrettyConfig::new())?;
+        fs::write(path, contents)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum Replay {
+    Idle,
+    Recording {
+        recording: InputRecording,
+        path: PathBuf,
+    },
+    Playing {
+        recording: InputRecording,
+        frame: usize,
+    },
+}
+
+impl Default for Replay {
+    fn default() -> Self {
+        Self::Idle
+    }
+}
+
+impl Replay {
+    pub fn record(path: impl Into<PathBuf>, step: f32) -> Self {
+        Self::Recording {
+            recording: InputRecording::new(step),
+            path: path.into(),
+        }
+    }
+
+    pub fn play(path: &Path) -> anyhow::Result<Self> {
+        Ok(Self::Playing {
+            recording: InputRecording::load(path)?,
+            frame: 0,
+        })
+    }
+
+    /// Reads `--record <path>` or `--replay <path>` from the command line.
+    pub fn from_args() -> Self {
+        let args = env::args().collect::<Vec<_>>();
+        for pair in args.windows(2) {
+            match pair[0].as_str() {
+                "--record" => {
+                    return Self::record(&pair[1], SimulationTime::default().delta_seconds())
+                }
+                "--replay" => match Self::play(Path::new(&pair[1])) {
+                    Ok(replay) => return replay,
+                    Err(error) => warn!("replay: could not read {}: {}", pair[1], error),
+                },
+                _ => {}
+            }
+        }
+        Self::Idle
+    }
+
+    pub fn step(&self) -> Option<f32> {
+        match self {
+            Replay::Idle => None,
+            Replay::Recording { recording, .. } | Replay::Playing { recording, .. } => {
+                Some(recording.step)
+            }
+        }
+    }
+
+    /// The recorded input for the current frame while playing back.
+    pub fn frame(&self) -> Option<&ReplayFrame> {
+        match self {
+            Replay::Playing { recording, frame } => recording.frames.get(*frame),
+            _ => None,
+        }
+    }
+}
+
+fn configure_simulation_time(replay: Res<Replay>, mut simulation: ResMut<SimulationTime>) {
+    if !replay.is_changed() {
+        return;
+    }
+
+    match replay.step() {
+        Some(step) => {
+            simulation.set_step(step);
+            simulation.set_lockstep(replay.frame().is_some());
+        }
+        None => simulation.set_lockstep(false),
+    }
+}
+
+fn record_frame(
+    mut replay: ResMut<Replay>,
+    actions: Res<ActionState>,
+    aim_source: Option<Res<AimSource>>,
+    cursor: Option<Res<CursorWorldPosition>>,
+) {
+    // Only borrow mutably while recording, so an idle replay isn't flagged as changed.
+    if !matches!(*replay, Replay::Recording { .. }) {
+        return;
+    }
+    if let Replay::Recording { recording, .. } = &mut *replay {
+        let local = actions.local();
+        let mut pressed = local.pressed_actions().collect::<Vec<_>>();
+        pressed.sort();
+
+        let time = recording.frames.len() as f64 * recording.step as f64;
+        recording.frames.push(ReplayFrame {
+            time,
+            pressed,
+            move_stick: local.move_stick(),
+            aim_stick: local.aim_stick().unwrap_or(Vec2::ZERO),
+            aim_source: aim_source.map_or(AimSource::default(), |source| *source),
+            cursor: cursor.as_ref().and_then(|cursor| cursor.get()),
+            cursor_offset: cursor.as_ref().and_then(|cursor| cursor.view_offset()),
+        });
+    }
+}
+
+fn advance_playback(mut replay: ResMut<Replay>) {
+    if !matches!(*replay, Replay::Playing { .. }) {
+        return;
+    }
+    let finished = match &mut *replay {
+        Replay::Playing { recording, frame } => {
+            *frame += 1;
+            *frame >= recording.frames.len()
+        }
+        _ => false,
+    };
+
+    if finished {
+        info!("replay: finished");
+        *replay = Replay::Idle;
+    }
+}
+
+fn save_recording(mut exit: EventReader<AppExit>, replay: Res<Replay>) {
+    if exit.iter().next().is_none() {
+        return;
+    }
+
+    if let Replay::Recording { recording, path } = &*replay {
+        match recording.save(path) {
+            Ok(()) => info!(
+                "replay: wrote {} frames to {}",
+                recording.frames.len(),
+                path.display()
+            ),
+            Err(error) => warn!("replay: could not write {}: {}", path.display(), error),
+        }
+    }
+}
+
+pub trait ReplayState: Debug + Clone + Copy + PartialEq + Eq + Hash + Sync + Send {}
+
+#[derive(Default)]
+pub struct ReplayPlugin<T: ReplayState> {
+    state: T,
+}
+
+impl<T: 'static + ReplayState> ReplayPlugin<T> {
+    pub fn new(state: T) -> Self {
+        Self { state }
+    }
+}
+
+impl<T: 'static + ReplayState> Plugin for ReplayPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Replay::from_args())
+            .add_system_to_stage(CoreStage::First, configure_simulation_time)
+            .add_system_to_stage(
+                FIXED_UPDATE,
+                record_frame
+                    .run_in_state(self.state)
+                    .after("update_action_state")
+                    .after("aim_source"),
+            )
+            .add_system_to_stage(CoreStage::Last, advance_playback.run_in_state(self.state))
+            .add_system_to_stage(CoreStage::Last, save_recording);
+    }
+}
@@ -0,0 +1,281 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use bevy::{
+    prelude::{warn, App, Plugin, Res, ResMut},
+    window::{PresentMode, WindowDescriptor},
+};
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    action::{Action, Binding, InputBindings},
+    camera::LookaheadMode,
+    dash::DashDirection,
+    input::SocdResolution,
+    mouse::AimMode,
+};
+
+const MIN_WINDOW_WIDTH: f32 = 320.0;
+const MIN_WINDOW_HEIGHT: f32 = 180.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowSettings {
+    pub width: f32,
+    pub height: f32,
+    pub vsync: bool,
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        Self {
+            width: 1280.0,
+            height: 720.0,
+            vsync: true,
+        }
+    }
+}
+
+/// How the player's character handles, applied when it spawns.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameplaySettings {
+    pub aim: AimMode,
+    pub camera_lookahead: LookaheadMode,
+    pub dash_direction: DashDirection,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub window: WindowSettings,
+    pub gameplay: GameplaySettings,
+    pub socd: SocdResolution,
+    pub bindings: BTreeMap<Action, Vec<Binding>>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            window: WindowSettings::default(),
+            gameplay: GameplaySettings::default(),
+            socd: SocdResolution::default(),
+            bindings: bindings_map(&InputBindings::default()),
+        }
+    }
+}
+
+impl Settings {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("goo").join("settings.ron"))
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        Ok(ron::from_str(&contents)?)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let contents = ron::ser::to_string_pretty(self, PrettyConfig::new())?;
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    /// Fixes up invalid or missing values. Returns warnings to report and whether
+    /// anything changed, in which case the settings should be saved again.
+    pub fn validate(&mut self) -> (Vec<String>, bool) {
+        let mut warnings = Vec::new();
+        let mut changed = false;
+
+        if !(self.window.width >= MIN_WINDOW_WIDTH && self.window.height >= MIN_WINDOW_HEIGHT) {
+            warnings.push(format!(
+                "window size {}x{} is too small, using at least {}x{}",
+                self.window.width, self.window.height, MIN_WINDOW_WIDTH, MIN_WINDOW_HEIGHT
+            ));
+            self.window.width = self.window.width.max(MIN_WINDOW_WIDTH);
+            self.window.height = self.window.height.max(MIN_WINDOW_HEIGHT);
+            changed = true;
+        }
+
+        let defaults = InputBindings::default();
+        for action in Action::ALL {
+            if !self.bindings.contains_key(&action) {
+                self.bindings
+                    .insert(action, defaults.bindings(action).to_vec());
+                changed = true;
+            }
+        }
+
+        warnings.extend(conflict_warnings(&self.input_bindings()));
+        (warnings, changed)
+    }
+
+    pub fn input_bindings(&self) -> InputBindings {
+        InputBindings::from_map(self.bindings.clone())
+    }
+
+    pub fn window_descriptor(&self) -> WindowDescriptor {
+        WindowDescriptor {
+            width: self.window.width,
+            height: self.window.height,
+            present_mode: if self.window.vsync {
+                PresentMode::Fifo
+            } else {
+                PresentMode::Immediate
+            },
+            ..Default::default()
+        }
+    }
+}
+
+fn bindings_map(bindings: &InputBindings) -> BTreeMap<Action, Vec<Binding>> {
+    bindings
+        .iter()
+        .map(|(action, bindings)| (action, bindings.to_vec()))
+        .collect()
+}
+
+fn conflict_warnings(bindings: &InputBindings) -> Vec<String> {
+    bindings
+        .conflicts()
+        .into_iter()
+        .map(|(binding, actions)| {
+            format!(
+                "{:?} is bound to more than one action: {:?}",
+                binding, actions
+            )
+        })
+        .collect()
+}
+
+/// Where [`Settings`] are written back to.
+struct SettingsFile {
+    path: Option<PathBuf>,
+    /// False when the file couldn't be read, so a broken one isn't overwritten with defaults.
+    writable: bool,
+}
+
+impl SettingsFile {
+    fn save(&self, settings: &Settings) {
+        let path = match &self.path {
+            Some(path) if self.writable => path,
+            _ => return,
+        };
+        if let Err(error) = settings.save(path) {
+            warn!("settings: could not write {}: {}", path.display(), error);
+        }
+    }
+}
+
+fn not_found(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<io::Error>()
+        .map_or(false, |error| error.kind() == io::ErrorKind::NotFound)
+}
+
+fn load_settings() -> (Settings, SettingsFile, Vec<String>) {
+    let path = match Settings::path() {
+        Some(path) => path,
+        None => {
+            let mut settings = Settings::default();
+            let (warnings, _) = settings.validate();
+            let file = SettingsFile {
+                path: None,
+                writable: false,
+            };
+            return (settings, file, warnings);
+        }
+    };
+
+    let mut warnings = Vec::new();
+    let mut writable = true;
+    let mut settings = match Settings::load(&path) {
+        Ok(settings) => settings,
+        Err(error) if not_found(&error) => {
+            let settings = Settings::default();
+            if let Err(error) = settings.save(&path) {
+                warnings.push(format!("could not write {}: {}", path.display(), error));
+            }
+            settings
+        }
+        Err(error) => {
+            warnings.push(format!(
+                "could not read {}, using defaults: {}",
+                path.display(),
+                error
+            ));
+            writable = false;
+            Settings::default()
+        }
+    };
+
+    let (validation_warnings, changed) = settings.validate();
+    warnings.extend(validation_warnings);
+    if changed && writable {
+        if let Err(error) = settings.save(&path) {
+            warnings.push(format!("could not write {}: {}", path.display(), error));
+        }
+    }
+
+    let file = SettingsFile {
+        path: Some(path),
+        writable,
+    };
+    (settings, file, warnings)
+}
+
+struct SettingsWarnings(Vec<String>);
+
+fn report_settings_warnings(warnings: Res<SettingsWarnings>) {
+    for warning in warnings.0.iter() {
+        warn!("settings: {}", warning);
+    }
+}
+
+fn save_settings(
+    bindings: Res<InputBindings>,
+    socd: Res<SocdResolution>,
+    file: Res<SettingsFile>,
+    mut settings: ResMut<Settings>,
+) {
+    let bindings_changed = bindings.is_changed() && !bindings.is_added();
+    let socd_changed = socd.is_changed() && !socd.is_added();
+    if !bindings_changed && !socd_changed {
+        return;
+    }
+
+    if bindings_changed {
+        for warning in conflict_warnings(&bindings) {
+            warn!("settings: {}", warning);
+        }
+    }
+
+    settings.bindings = bindings_map(&bindings);
+    settings.socd = *socd;
+    file.save(&settings);
+}
+
+#[derive(Default)]
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        let (settings, file, warnings) = load_settings();
+
+        app.insert_resource(settings.window_descriptor())
+            .insert_resource(settings.input_bindings())
+            .insert_resource(settings.socd)
+            .insert_resource(settings)
+            .insert_resource(file)
+            .insert_resource(SettingsWarnings(warnings))
+            .add_startup_system(report_settings_warnings)
+            .add_system(save_settings);
+    }
+}
@@ -0,0 +1,257 @@
+use std::time::Duration;
+
+use bevy::{
+    core::Time,
+    ecs::schedule::ShouldRun,
+    math::{Quat, Vec3},
+    prelude::{
+        App, Component, CoreStage, Local, ParallelSystemDescriptorCoercion, Plugin, Query, Res,
+        ResMut, SystemStage, Transform,
+    },
+};
+
+/// Stage that runs gameplay at a fixed rate, zero or more times per frame.
+pub const FIXED_UPDATE: &str = "fixed_update";
+
+const DEFAULT_STEP: f32 = 1.0 / 60.0;
+const MAX_FRAME_TIME: Duration = Duration::from_millis(250);
+
+/// Clock for systems in [`FIXED_UPDATE`]. In lockstep the stage runs exactly once per
+/// frame regardless of frame time, which replay uses to reproduce a recording tick for tick.
+#[derive(Debug)]
+pub struct SimulationTime {
+    step: Duration,
+    accumulator: Duration,
+    lockstep: bool,
+}
+
+impl Default for SimulationTime {
+    fn default() -> Self {
+        Self {
+            step: Duration::from_secs_f32(DEFAULT_STEP),
+            accumulator: Duration::ZERO,
+            lockstep: false,
+        }
+    }
+}
+
+impl SimulationTime {
+    pub fn delta(&self) -> Duration {
+        self.step
+    }
+
+    pub fn delta_seconds(&self) -> f32 {
+        self.step.as_secs_f32()
+    }
+
+    pub fn set_step(&mut self, step: f32) {
+        self.step = Duration::from_secs_f32(step);
+    }
+
+    /// Switching modes drops any partial tick; setting the current mode again keeps it.
+    pub fn set_lockstep(&mut self, lockstep: bool) {
+        if self.lockstep != lockstep {
+            self.lockstep = lockstep;
+            self.accumulator = Duration::ZERO;
+        }
+    }
+
+    /// How far the frame is between the last tick and the next, from 0 to 1.
+    pub fn overstep(&self) -> f32 {
+        if self.lockstep {
+            1.0
+        } else {
+            self.accumulator.as_secs_f32() / self.step.as_secs_f32()
+        }
+    }
+
+    /// One check of the fixed stage's run criteria. `looping` is true while the stage is
+    /// being re-run within the same frame.
+    fn next_tick(&mut self, frame_time: Duration, looping: &mut bool) -> ShouldRun {
+        if self.lockstep {
+            *looping = !*looping;
+            if !*looping {
+                return ShouldRun::No;
+            }
+            return ShouldRun::YesAndCheckAgain;
+        }
+
+        if !*looping {
+            self.accumulator += frame_time.min(MAX_FRAME_TIME);
+        }
+
+        if self.accumulator >= self.step {
+            self.accumulator -= self.step;
+            *looping = true;
+            ShouldRun::YesAndCheckAgain
+        } else {
+            *looping = false;
+            ShouldRun::No
+        }
+    }
+}
+
+fn run_fixed_step(
+    time: Res<Time>,
+    mut simulation: ResMut<SimulationTime>,
+    mut looping: Local<bool>,
+) -> ShouldRun {
+    simulation.next_tick(time.delta(), &mut looping)
+}
+
+/// Smooths the translation and rotation of an entity moved in [`FIXED_UPDATE`] by
+/// blending between its last two simulated transforms every frame.
+#[derive(Component, Debug, Default)]
+pub struct Interpolated {
+    previous: Vec3,
+    current: Vec3,
+    previous_rotation: Quat,
+    current_rotation: Quat,
+    initialized: bool,
+}
+
+impl Interpolated {
+    /// Forgets the simulated positions so the next tick starts fresh, e.g. after a teleport.
+    pub fn reset(&mut self) {
+        self.initialized = false;
+    }
+}
+
+fn restore_transforms(mut query: Query<(&mut Transform, &Interpolated)>) {
+    for (mut transform, interpolated) in query.iter_mut() {
+        if interpolated.initialized {
+            transform.translation = interpolated.current;
+            transform.rotation = interpolated.current_rotation;
+        }
+    }
+}
+
+fn store_transforms(mut query: Query<(&Transform, &mut Interpolated)>) {
+    for (transform, mut interpolated) in query.iter_mut() {
+        if interpolated.initialized {
+            interpolated.previous = interpolated.current;
+            interpolated.previous_rotation = interpolated.current_rotation;
+        } else {
+            interpolated.previous = transform.translation;
+            interpolated.previous_rotation = transform.rotation;
+        }
+        interpolated.current = transform.translation;
+        interpolated.current_rotation = transform.rotation;
+        interpolated.initialized = true;
+    }
+}
+
+fn interpolate_transforms(
+    simulation: Res<SimulationTime>,
+    mut query: Query<(&mut Transform, &Interpolated)>,
+) {
+    let alpha = simulation.overstep().clamp(0.0, 1.0);
+    for (mut transform, interpolated) in query.iter_mut() {
+        if interpolated.initialized {
+            transform.translation = interpolated.previous.lerp(interpolated.current, alpha);
+            transform.rotation = interpolated
+                .previous_rotation
+                .slerp(interpolated.current_rotation, alpha);
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SimulationTime>()
+            .add_stage_before(
+                CoreStage::Update,
+                FIXED_UPDATE,
+                SystemStage::parallel().with_run_criteria(run_fixed_step),
+            )
+            .add_system_to_stage(CoreStage::PreUpdate, restore_transforms)
+            .add_system_to_stage(
+                FIXED_UPDATE,
+                store_transforms.after("player_movement").after("apply_aim"),
+            )
+            .add_system_to_stage(
+                CoreStage::Update,
+                interpolate_transforms
+                    .label("interpolate_transforms")
+                    .before("camera_movement"),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs the criteria like a stage would for one frame and counts the ticks.
+    fn frame(simulation: &mut SimulationTime, looping: &mut bool, frame_time: Duration) -> u32 {
+        let mut ticks = 0;
+        while simulation.next_tick(frame_time, looping) == ShouldRun::YesAndCheckAgain {
+            ticks += 1;
+        }
+        ticks
+    }
+
+    fn ticks_over(simulation: &mut SimulationTime, frames: u32, frame_time: Duration) -> u32 {
+        let mut looping = false;
+        (0..frames)
+            .map(|_| frame(simulation, &mut looping, frame_time))
+            .sum()
+    }
+
+    #[test]
+    fn fast_frames_accumulate_into_ticks() {
+        let mut simulation = SimulationTime::default();
+        let ticks = ticks_over(&mut simulation, 144, Duration::from_secs_f64(1.0 / 144.0));
+        assert!((59..=60).contains(&ticks), "{}", ticks);
+    }
+
+    #[test]
+    fn slow_frames_run_several_ticks() {
+        let mut simulation = SimulationTime::default();
+        let mut looping = false;
+        let frame_time = Duration::from_secs_f64(1.0 / 30.0);
+
+        let ticks = (0..30)
+            .map(|_| frame(&mut simulation, &mut looping, frame_time))
+            .collect::<Vec<_>>();
+
+        assert!(
+            ticks.iter().all(|ticks| (1..=2).contains(ticks)),
+            "{:?}",
+            ticks
+        );
+        assert!(
+            (59..=60).contains(&ticks.iter().sum::<u32>()),
+            "{:?}",
+            ticks
+        );
+    }
+
+    #[test]
+    fn setting_the_same_mode_keeps_partial_ticks() {
+        let mut simulation = SimulationTime::default();
+        let mut looping = false;
+        let frame_time = Duration::from_secs_f64(1.0 / 144.0);
+
+        let ticks = (0..144)
+            .map(|_| {
+                simulation.set_lockstep(false);
+                frame(&mut simulation, &mut looping, frame_time)
+            })
+            .sum::<u32>();
+
+        assert!((59..=60).contains(&ticks), "{}", ticks);
+    }
+
+    #[test]
+    fn lockstep_runs_one_tick_per_frame() {
+        let mut simulation = SimulationTime::default();
+        simulation.set_lockstep(true);
+
+        assert_eq!(ticks_over(&mut simulation, 10, Duration::from_secs(1)), 10);
+        assert_eq!(ticks_over(&mut simulation, 10, Duration::ZERO), 10);
+    }
+}
@@ -0,0 +1,160 @@
+use std::{fmt::Debug, hash::Hash};
+
+use bevy::{
+    core::Timer,
+    math::Vec2,
+    prelude::{App, Component, ParallelSystemDescriptorCoercion, Plugin, Query, Res},
+};
+use iyes_loopless::prelude::IntoConditionalSystem;
+
+use crate::{
+    action::{Action, ActionState},
+    input::{Controlled, MoveInput},
+    simulation::{SimulationTime, FIXED_UPDATE},
+};
+
+#[derive(Component, Debug)]
+pub struct Sprint {
+    pub multiplier: f32,
+    pub drain: f32,
+    sprinting: bool,
+}
+
+impl Default for Sprint {
+    fn default() -> Self {
+        Self::new(1.6, 30.0)
+    }
+}
+
+impl Sprint {
+    pub fn new(multiplier: f32, drain: f32) -> Self {
+        Self {
+            multiplier,
+            drain,
+            sprinting: false,
+        }
+    }
+
+    pub fn sprinting(&self) -> bool {
+        self.sprinting
+    }
+
+    pub fn speed_multiplier(&self) -> f32 {
+        if self.sprinting {
+            self.multiplier
+        } else {
+            1.0
+        }
+    }
+}
+
+#[derive(Component, Debug)]
+pub struct Stamina {
+    pub max: f32,
+    pub regen_rate: f32,
+    pub recover_fraction: f32,
+    current: f32,
+    exhausted: bool,
+    regen_delay: Timer,
+}
+
+impl Default for Stamina {
+    fn default() -> Self {
+        Self::new(100.0, 25.0, 1.0)
+    }
+}
+
+impl Stamina {
+    pub fn new(max: f32, regen_rate: f32, regen_delay: f32) -> Self {
+        Self {
+            max,
+            regen_rate,
+            recover_fraction: 0.3,
+            current: max,
+            exhausted: false,
+            regen_delay: Timer::from_seconds(regen_delay, false),
+        }
+    }
+
+    pub fn current(&self) -> f32 {
+        self.current
+    }
+
+    pub fn fraction(&self) -> f32 {
+        if self.max > 0.0 {
+            self.current / self.max
+        } else {
+            0.0
+        }
+    }
+
+    pub fn exhausted(&self) -> bool {
+        self.exhausted
+    }
+
+    pub fn drain(&mut self, amount: f32) {
+        self.current = (self.current - amount).max(0.0);
+        self.regen_delay.reset();
+        if self.current <= 0.0 {
+            self.exhausted = true;
+        }
+    }
+
+    fn regenerate(&mut self, time: &SimulationTime) {
+        if !self.regen_delay.tick(time.delta()).finished() {
+            return;
+        }
+
+        self.current = (self.current + self.regen_rate * time.delta_seconds()).min(self.max);
+        if self.exhausted && self.fraction() >= self.recover_fraction {
+            self.exhausted = false;
+        }
+    }
+}
+
+fn update_sprint(
+    time: Res<SimulationTime>,
+    actions: Res<ActionState>,
+    mut query: Query<(&mut Sprint, &mut Stamina, &MoveInput, &Controlled)>,
+) {
+    for (mut sprint, mut stamina, input, controlled) in query.iter_mut() {
+        let pressed = actions
+            .source(controlled.0)
+            .map_or(false, |actions| actions.pressed(Action::Sprint));
+        sprint.sprinting =
+            pressed && input.0 != Vec2::ZERO && !stamina.exhausted() && stamina.current() > 0.0;
+
+        if sprint.sprinting {
+            let drain = sprint.drain * time.delta_seconds();
+            stamina.drain(drain);
+        } else {
+            stamina.regenerate(&time);
+        }
+    }
+}
+
+pub trait SprintState: Debug + Clone + Copy + PartialEq + Eq + Hash + Sync + Send {}
+
+#[derive(Default)]
+pub struct SprintPlugin<T: SprintState> {
+    state: T,
+}
+
+impl<T: 'static + SprintState> SprintPlugin<T> {
+    pub fn new(state: T) -> Self {
+        Self { state }
+    }
+}
+
+impl<T: 'static + SprintState> Plugin for SprintPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.add_system_to_stage(
+            FIXED_UPDATE,
+            update_sprint
+                .run_in_state(self.state)
+                .after("update_action_state")
+                .after("follow_move_target")
+                .before("apply_move_input"),
+        );
+    }
+}
@@ -0,0 +1,92 @@
+use bevy::{
+    core::Name,
+    math::{Quat, Vec2},
+    prelude::{
+        error, App, Changed, CoreStage, Entity, Local, ParallelSystemDescriptorCoercion, Plugin,
+        Query, RemovedComponents, Transform,
+    },
+    transform::TransformSystem,
+    utils::HashMap,
+};
+
+use crate::{input::Velocity, simulation::Interpolated};
+
+fn transform_is_valid(transform: &Transform) -> bool {
+    transform.translation.is_finite()
+        && transform.rotation.is_finite()
+        && transform.rotation.length_squared() > 0.0
+        && transform.scale.is_finite()
+}
+
+/// Finds transforms that went non-finite and puts them back to their last valid value,
+/// so one bad division can't leave an entity invisible or stuck for the rest of the game.
+fn repair_transforms(
+    mut last_valid: Local<HashMap<Entity, Transform>>,
+    removed: RemovedComponents<Transform>,
+    mut query: Query<
+        (
+            Entity,
+            &mut Transform,
+            Option<&mut Velocity>,
+            Option<&mut Interpolated>,
+            Option<&Name>,
+        ),
+        Changed<Transform>,
+    >,
+) {
+    for entity in removed.iter() {
+        last_valid.remove(&entity);
+    }
+
+    for (entity, mut transform, velocity, interpolated, name) in query.iter_mut() {
+        if transform_is_valid(&transform) {
+            last_valid.insert(entity, *transform);
+            continue;
+        }
+
+        error!(
+            "repairing invalid transform on {:?} ({}): {:?}",
+            entity,
+            name.map_or("unnamed", |name| name.as_str()),
+            *transform
+        );
+
+        let fallback = last_valid.get(&entity).copied().unwrap_or_default();
+        if !transform.translation.is_finite() {
+            transform.translation = fallback.translation;
+        }
+        if !transform.rotation.is_finite() || transform.rotation.length_squared() == 0.0 {
+            transform.rotation = if fallback.rotation.is_finite() {
+                fallback.rotation
+            } else {
+                Quat::IDENTITY
+            };
+        }
+        if !transform.scale.is_finite() {
+            transform.scale = fallback.scale;
+        }
+        transform.rotation = transform.rotation.normalize();
+
+        if let Some(mut velocity) = velocity {
+            if !velocity.0.is_finite() {
+                velocity.0 = Vec2::ZERO;
+            }
+        }
+        if let Some(mut interpolated) = interpolated {
+            interpolated.reset();
+        }
+        last_valid.insert(entity, *transform);
+    }
+}
+
+#[derive(Default)]
+pub struct ValidationPlugin;
+
+impl Plugin for ValidationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_to_stage(
+            CoreStage::PostUpdate,
+            repair_transforms.before(TransformSystem::TransformPropagate),
+        );
+    }
+}
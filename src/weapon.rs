@@ -0,0 +1,299 @@
+use std::{fmt::Debug, hash::Hash};
+
+use bevy::{
+    core::Timer,
+    math::{Quat, Vec2, Vec3},
+    prelude::{
+        App, Color, Commands, Component, Entity, EventWriter, ParallelSystemDescriptorCoercion,
+        Plugin, Query, Res, ResMut, Sprite, SpriteBundle, Transform, With, Without,
+    },
+};
+use iyes_loopless::prelude::IntoConditionalSystem;
+
+use crate::{
+    action::{Action, ActionState, InputSource},
+    collision::{contact, Collider, TileMap},
+    combat::{Damage, Health},
+    crosshair::CrosshairState,
+    input::{Controlled, Player, Velocity},
+    simulation::{Interpolated, SimulationTime, FIXED_UPDATE},
+};
+
+#[derive(Component, Debug)]
+pub struct Weapon {
+    /// Shots per second.
+    pub fire_rate: f32,
+    pub automatic: bool,
+    pub damage: f32,
+    pub projectile_speed: f32,
+    pub projectile_lifetime: f32,
+    pub projectile_radius: f32,
+    pub projectiles_per_shot: u32,
+    /// Full cone angle the shots are scattered across.
+    pub spread_degrees: f32,
+    pub magazine_size: u32,
+    pub reload_seconds: f32,
+    rounds: u32,
+    cooldown: Timer,
+    reload: Option<Timer>,
+    seed: u32,
+}
+
+impl Default for Weapon {
+    fn default() -> Self {
+        Self::new(8.0, 10.0, 600.0)
+    }
+}
+
+impl Weapon {
+    pub fn new(fire_rate: f32, damage: f32, projectile_speed: f32) -> Self {
+        let mut cooldown = Timer::from_seconds(1.0 / fire_rate, false);
+        cooldown.tick(cooldown.duration());
+
+        Self {
+            fire_rate,
+            automatic: true,
+            damage,
+            projectile_speed,
+            projectile_lifetime: 1.0,
+            projectile_radius: 3.0,
+            projectiles_per_shot: 1,
+            spread_degrees: 4.0,
+            magazine_size: 24,
+            reload_seconds: 1.2,
+            rounds: 24,
+            cooldown,
+            reload: None,
+            seed: 0x9e37_79b9,
+        }
+    }
+
+    pub fn start_reload(&mut self) {
+        if self.reload.is_none() && self.rounds < self.magazine_size {
+            self.reload = Some(Timer::from_seconds(self.reload_seconds, false));
+        }
+    }
+
+    fn ready(&self) -> bool {
+        self.cooldown.finished() && self.reload.is_none() && self.rounds > 0
+    }
+
+    /// Deterministic xorshift so replays scatter shots the same way.
+    fn next_random(&mut self) -> f32 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        self.seed as f32 / u32::MAX as f32
+    }
+}
+
+#[derive(Component, Debug)]
+pub struct Projectile {
+    pub owner: Entity,
+    pub damage: f32,
+    pub radius: f32,
+    lifetime: Timer,
+}
+
+fn weapon_timers(time: Res<SimulationTime>, mut query: Query<&mut Weapon>) {
+    for mut weapon in query.iter_mut() {
+        weapon.cooldown.tick(time.delta());
+
+        let finished = match weapon.reload.as_mut() {
+            Some(reload) => reload.tick(time.delta()).finished(),
+            None => false,
+        };
+        if finished {
+            weapon.reload = None;
+            weapon.rounds = weapon.magazine_size;
+        }
+    }
+}
+
+fn fire_weapons(
+    mut commands: Commands,
+    actions: Res<ActionState>,
+    mut query: Query<(Entity, &Controlled, &Transform, &mut Weapon)>,
+) {
+    for (entity, controlled, transform, mut weapon) in query.iter_mut() {
+        let actions = match actions.source(controlled.0) {
+            Some(actions) => actions,
+            None => continue,
+        };
+
+        if actions.just_pressed(Action::Reload) {
+            weapon.start_reload();
+        }
+
+        let firing = if weapon.automatic {
+            actions.pressed(Action::Fire)
+        } else {
+            actions.just_pressed(Action::Fire)
+        };
+        if !firing || !weapon.ready() {
+            if firing && weapon.rounds == 0 {
+                weapon.start_reload();
+            }
+            continue;
+        }
+
+        weapon.rounds -= 1;
+        let fire_rate = weapon.fire_rate;
+        weapon.cooldown = Timer::from_seconds(1.0 / fire_rate, false);
+
+        let facing = transform.rotation.mul_vec3(Vec3::X).truncate();
+        let facing_angle = facing.y.atan2(facing.x);
+        let muzzle = transform.translation.truncate() + facing * 16.0;
+
+        for _ in 0..weapon.projectiles_per_shot.max(1) {
+            let scatter = (weapon.next_random() - 0.5) * weapon.spread_degrees.to_radians();
+            let angle = facing_angle + scatter;
+            let direction = Vec2::new(angle.cos(), angle.sin());
+
+            commands
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::rgb(1.0, 0.9, 0.4),
+                        custom_size: Some(Vec2::new(weapon.projectile_radius * 3.0, 2.0)),
+                        ..Default::default()
+                    },
+                    transform: Transform::from_translation(muzzle.extend(10.0))
+                        .with_rotation(Quat::from_rotation_z(angle)),
+                    ..Default::default()
+                })
+                .insert(Projectile {
+                    owner: entity,
+                    damage: weapon.damage,
+                    radius: weapon.projectile_radius,
+                    lifetime: Timer::from_seconds(weapon.projectile_lifetime, false),
+                })
+                .insert(Velocity(direction * weapon.projectile_speed))
+                .insert(Interpolated::default());
+        }
+
+        if weapon.rounds == 0 {
+            weapon.start_reload();
+        }
+    }
+}
+
+fn projectile_hits(
+    mut commands: Commands,
+    time: Res<SimulationTime>,
+    mut damage: EventWriter<Damage>,
+    mut projectiles: Query<(Entity, &Transform, &Velocity, &mut Projectile)>,
+    colliders: Query<(Entity, &Transform, &Collider, Option<&Health>), Without<Projectile>>,
+    tilemaps: Query<(&Transform, &TileMap), Without<Projectile>>,
+) {
+    let delta = time.delta_seconds();
+    for (entity, transform, velocity, mut projectile) in projectiles.iter_mut() {
+        if projectile.lifetime.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        // Check along the whole tick's motion so fast shots can't pass through thin targets.
+        let end = transform.translation.truncate();
+        let start = end - velocity.0 * delta;
+        let shape = Collider::circle(projectile.radius);
+        let samples = ((end - start).length() / projectile.radius.max(1.0))
+            .ceil()
+            .max(1.0) as usize;
+
+        let hit = (0..=samples).find_map(|sample| {
+            let position = start.lerp(end, sample as f32 / samples as f32);
+            let body = colliders
+                .iter()
+                .filter(|(other, ..)| *other != projectile.owner)
+                .find(|(_, other_transform, collider, _)| {
+                    contact(
+                        &shape,
+                        position,
+                        collider,
+                        other_transform.translation.truncate(),
+                    )
+                    .is_some()
+                })
+                .map(|(other, _, _, health)| health.map(|_| other));
+            let wall = || {
+                tilemaps.iter().any(|(map_transform, map)| {
+                    let origin = map_transform.translation.truncate();
+                    map.colliders_in(
+                        origin,
+                        position - projectile.radius,
+                        position + projectile.radius,
+                    )
+                    .any(|(tile, collider)| contact(&shape, position, &collider, tile).is_some())
+                })
+            };
+            body.or_else(|| wall().then(|| None))
+        });
+
+        if let Some(target) = hit {
+            if let Some(target) = target {
+                damage.send(Damage {
+                    target,
+                    amount: projectile.damage,
+                    source: Some(projectile.owner),
+                });
+            }
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn crosshair_spread(
+    mut crosshair: ResMut<CrosshairState>,
+    query: Query<(&Weapon, Option<&Controlled>), With<Player>>,
+) {
+    crosshair.spread = query
+        .iter()
+        .find(|(_, controlled)| {
+            controlled.map_or(true, |controlled| controlled.0 == InputSource::Local)
+        })
+        .map_or(0.0, |(weapon, _)| weapon.spread_degrees.to_radians() * 0.5);
+}
+
+pub trait WeaponState: Debug + Clone + Copy + PartialEq + Eq + Hash + Sync + Send {}
+
+#[derive(Default)]
+pub struct WeaponPlugin<T: WeaponState> {
+    state: T,
+}
+
+impl<T: 'static + WeaponState> WeaponPlugin<T> {
+    pub fn new(state: T) -> Self {
+        Self { state }
+    }
+}
+
+impl<T: 'static + WeaponState> Plugin for WeaponPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.add_system_to_stage(
+            FIXED_UPDATE,
+            weapon_timers
+                .run_in_state(self.state)
+                .before("fire_weapons"),
+        )
+        .add_system_to_stage(
+            FIXED_UPDATE,
+            fire_weapons
+                .run_in_state(self.state)
+                .label("fire_weapons")
+                .after("update_action_state")
+                .after("apply_aim"),
+        )
+        .add_system_to_stage(
+            FIXED_UPDATE,
+            projectile_hits
+                .run_in_state(self.state)
+                .after("player_movement")
+                .before("apply_damage"),
+        )
+        .add_system(
+            crosshair_spread
+                .run_in_state(self.state)
+                .before("update_crosshair"),
+        );
+    }
+}